@@ -0,0 +1,226 @@
+//! Single-file copy of a project, for moving it between machines.
+
+use std::collections::HashSet;
+
+use chrono::{DateTime, Local};
+use serde::{Deserialize, Serialize};
+
+use crate::error::{PmError, Result};
+use crate::history::HistoryEntry;
+use crate::models::{Project, Task};
+use crate::taskwarrior;
+use crate::workflow::Workflow;
+
+/// Value of [`Bundle::format`], so other JSON files are not mistaken for a
+/// bundle.
+pub const FORMAT: &str = "pmcli-bundle";
+
+/// Schema version written by this build. Bundles with a higher version are
+/// refused; fields added later must default so older bundles still load.
+pub const VERSION: u32 = 1;
+
+/// Everything stored for a project except the lock and the undo journal,
+/// which only make sense on the machine that wrote them.
+#[derive(Serialize, Deserialize, Debug, Clone)]
+pub struct Bundle {
+    pub format: String,
+    pub version: u32,
+    pub exported_at: DateTime<Local>,
+    /// Contents of `project.json`.
+    pub project: Project,
+    pub tasks: Vec<Task>,
+    #[serde(default)]
+    pub archive: Vec<Task>,
+    /// Contents of `notes.md`.
+    #[serde(default)]
+    pub notes: String,
+    /// `None` when the project uses the built-in workflow.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub workflow: Option<Workflow>,
+    #[serde(default)]
+    pub history: Vec<HistoryEntry>,
+}
+
+impl Bundle {
+    /// A bundle of a project with only active tasks, as when importing
+    /// from another tool.
+    pub fn new(project: Project, tasks: Vec<Task>) -> Bundle {
+        Bundle {
+            format: FORMAT.to_string(),
+            version: VERSION,
+            exported_at: Local::now(),
+            project,
+            tasks,
+            archive: Vec::new(),
+            notes: String::new(),
+            workflow: None,
+            history: Vec::new(),
+        }
+    }
+
+    /// Read a bundle, checking its format and version before the contents so
+    /// a newer bundle gets a clear error instead of a parse failure.
+    pub fn parse(data: &str) -> Result<Bundle> {
+        #[derive(Deserialize)]
+        struct Header {
+            format: Option<String>,
+            version: Option<u32>,
+        }
+
+        let header: Header = serde_json::from_str(data).map_err(invalid)?;
+
+        if header.format.as_deref() != Some(FORMAT) {
+            return Err(PmError::InvalidInput("not a pmcli bundle".into()));
+        }
+        match header.version {
+            Some(v) if v <= VERSION => {}
+            Some(v) => {
+                return Err(PmError::InvalidInput(format!(
+                    "bundle version {} is newer than this pmcli supports ({}), please upgrade",
+                    v, VERSION
+                )))
+            }
+            None => return Err(PmError::InvalidInput("bundle has no version".into())),
+        }
+
+        let bundle: Bundle = serde_json::from_str(data).map_err(invalid)?;
+        bundle.validate()?;
+        Ok(bundle)
+    }
+
+    /// Give every task without a UUID the one derived from the project and
+    /// its ID, so merging the bundle again updates those tasks instead of
+    /// adding copies.
+    pub fn assign_uuids(&mut self) {
+        for task in self.tasks.iter_mut().chain(self.archive.iter_mut()) {
+            if task.uuid.is_none() {
+                task.uuid = Some(taskwarrior::uuid(&self.project.name, task));
+            }
+        }
+    }
+
+    pub fn to_json(&self) -> Result<String> {
+        let mut json = serde_json::to_string_pretty(self).map_err(std::io::Error::from)?;
+        json.push('\n');
+        Ok(json)
+    }
+
+    fn validate(&self) -> Result<()> {
+        let mut ids = HashSet::new();
+        let mut uuids = HashSet::new();
+
+        for task in self.tasks.iter().chain(&self.archive) {
+            if !ids.insert(task.id) {
+                return Err(PmError::InvalidInput(format!(
+                    "invalid bundle: task ID {} appears twice",
+                    task.id
+                )));
+            }
+            if let Some(uuid) = &task.uuid {
+                if !uuids.insert(uuid) {
+                    return Err(PmError::InvalidInput(format!(
+                        "invalid bundle: UUID {} appears twice",
+                        uuid
+                    )));
+                }
+            }
+        }
+
+        if let Some(workflow) = &self.workflow {
+            workflow
+                .validate()
+                .map_err(|e| PmError::InvalidInput(format!("invalid bundle: {}", e)))?;
+        }
+
+        Ok(())
+    }
+}
+
+fn invalid(e: serde_json::Error) -> PmError {
+    PmError::InvalidInput(format!("invalid bundle: {}", e))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::models::{Priority, Status};
+    use crate::test_support;
+
+    fn bundle() -> Bundle {
+        let task = Task {
+            priority: Priority::High,
+            tags: vec!["api".into()],
+            ..test_support::task(1, Status::Todo)
+        };
+        let mut archived = task.clone();
+        archived.id = 2;
+        archived.archived_at = Some(Local::now());
+
+        Bundle {
+            format: FORMAT.to_string(),
+            version: VERSION,
+            exported_at: Local::now(),
+            project: Project {
+                name: "web".into(),
+                created_at: Local::now(),
+                next_task_id: 3,
+            },
+            tasks: vec![task],
+            archive: vec![archived],
+            notes: "- remember\n".into(),
+            workflow: None,
+            history: Vec::new(),
+        }
+    }
+
+    fn error(json: &str) -> String {
+        Bundle::parse(json).unwrap_err().to_string()
+    }
+
+    #[test]
+    fn reads_back_what_it_writes() {
+        let bundle = bundle();
+
+        let parsed = Bundle::parse(&bundle.to_json().unwrap()).unwrap();
+
+        assert_eq!(parsed.project.name, "web");
+        assert_eq!(parsed.tasks, bundle.tasks);
+        assert_eq!(parsed.archive, bundle.archive);
+        assert_eq!(parsed.notes, bundle.notes);
+        assert!(parsed.workflow.is_none());
+    }
+
+    #[test]
+    fn checks_the_header_first() {
+        assert_eq!(error(r#"{"tasks":[]}"#), "not a pmcli bundle");
+        assert_eq!(
+            error(r#"{"format":"pmcli-bundle"}"#),
+            "bundle has no version"
+        );
+        assert!(error(r#"{"format":"pmcli-bundle","version":99}"#).contains("please upgrade"));
+        assert!(error("[1, 2]").starts_with("invalid bundle:"));
+    }
+
+    #[test]
+    fn rejects_duplicate_ids() {
+        let mut bundle = bundle();
+        bundle.archive[0].id = 1;
+
+        assert_eq!(
+            error(&bundle.to_json().unwrap()),
+            "invalid bundle: task ID 1 appears twice"
+        );
+    }
+
+    #[test]
+    fn rejects_duplicate_uuids() {
+        let mut bundle = bundle();
+        bundle.tasks[0].uuid = Some("a1".into());
+        bundle.archive[0].uuid = Some("a1".into());
+
+        assert_eq!(
+            error(&bundle.to_json().unwrap()),
+            "invalid bundle: UUID a1 appears twice"
+        );
+    }
+}
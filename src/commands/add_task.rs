@@ -1,47 +1,124 @@
-use chrono::NaiveDate;
-use dirs::home_dir;
-use std::env;
-use std::fs;
+use chrono::Local;
 
 use crate::config;
+use crate::error::{PmError, Result};
 use crate::models::{Status, Task};
+use crate::storage::ProjectStore;
+use crate::tags;
 
-pub fn run(project: &str, desc: &str, priority: Option<&str>, deadline: Option<&str>) {
-    let path = home_dir()
-        .unwrap()
-        .join(".pmcli")
-        .join(project)
-        .join("tasks.json");
+/// Add a task, optionally as a subtask of `parent`, and return it with its
+/// assigned ID.
+pub fn run(
+    project: &str,
+    desc: &str,
+    priority: Option<&str>,
+    deadline: Option<&str>,
+    parent: Option<u32>,
+    tags: &[String],
+) -> Result<Task> {
+    let store = ProjectStore::open(project)?;
 
-    let mut tasks: Vec<Task> = serde_json::from_str(&fs::read_to_string(&path).unwrap()).unwrap();
+    let mut task_tags: Vec<String> = Vec::new();
+    for tag in tags {
+        let tag = tags::normalize(tag)?;
+        if !task_tags.contains(&tag) {
+            task_tags.push(tag);
+        }
+    }
 
-    let cfg = config::load();
+    let cfg = config::load()?;
 
-    let date_format = cfg.date_format.as_deref().unwrap_or("%Y-%m-%d");
+    let parsed_deadline = deadline.map(|d| config::parse_date(&cfg, d)).transpose()?;
 
-    let parsed_deadline = deadline.map(|d| {
-        NaiveDate::parse_from_str(d, date_format).expect("Invalid date format (check config)")
-    });
+    let prio = match priority {
+        Some(p) => p.parse().map_err(PmError::InvalidInput)?,
+        None => cfg.default_priority.unwrap_or_default(),
+    };
 
-    let prio = priority
-        .map(|p| p.to_string())
-        .or(cfg.default_priority)
-        .unwrap_or_else(|| "medium".to_string());
+    let owner = config::current_user();
 
-    let owner = env::var("USER").unwrap_or_else(|_| "unknown".to_string());
+    add(
+        &store,
+        Task {
+            id: 0,
+            description: desc.to_string(),
+            status: Status::Todo,
+            priority: prio,
+            deadline: parsed_deadline,
+            owner,
+            parent,
+            depends_on: Vec::new(),
+            tags: task_tags,
+            created_at: Some(Local::now()),
+            updated_at: None,
+            completed_at: None,
+            archived_at: None,
+            uuid: None,
+        },
+    )
+}
+
+/// Store `task` under a fresh ID, starting at the first status of the
+/// project's workflow.
+fn add(store: &ProjectStore, task: Task) -> Result<Task> {
+    let workflow = store.load_workflow()?;
+
+    store.update_tasks(|tasks| {
+        if let Some(parent) = task.parent {
+            if !tasks.iter().any(|t| t.id == parent) {
+                return Err(PmError::TaskNotFound(parent));
+            }
+        }
+
+        let task = Task {
+            id: store.allocate_task_id(tasks)?,
+            status: workflow.initial().clone(),
+            ..task
+        };
+
+        tasks.push(task.clone());
+        Ok(task)
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::commands::set_status;
+    use crate::test_support::{self, temp_store};
+    use crate::workflow::Workflow;
+
+    #[test]
+    fn new_tasks_start_at_the_first_workflow_status() {
+        let store = temp_store("add-task");
+        let workflow: Workflow = toml::from_str(
+            r#"
+            [[status]]
+            name = "Backlog"
+
+            [[status]]
+            name = "Review"
 
-    let id = tasks.len() as u32 + 1;
+            [[status]]
+            name = "Shipped"
+            kind = "done"
 
-    tasks.push(Task {
-        id,
-        description: desc.to_string(),
-        status: Status::Todo,
-        priority: prio,
-        deadline: parsed_deadline,
-        owner,
-    });
+            [transitions]
+            Backlog = ["Review"]
+            Review = ["Backlog", "Shipped"]
+            "#,
+        )
+        .unwrap();
+        store.save_workflow(&workflow).unwrap();
 
-    fs::write(&path, serde_json::to_string_pretty(&tasks).unwrap()).unwrap();
+        let task = add(&store, test_support::task(0, Status::Todo)).unwrap();
 
-    println!("➕ Task added");
+        assert_eq!(task.id, 1);
+        assert_eq!(task.status, Status::Custom("Backlog".into()));
+        let err = set_status::set(&store, 1, "shipped", false).unwrap_err();
+        assert!(err.to_string().contains("does not allow"), "{}", err);
+        let task = set_status::set(&store, 1, "review", false).unwrap();
+        assert_eq!(task.status, Status::Custom("Review".into()));
+        std::fs::remove_dir_all(store.dir()).ok();
+    }
 }
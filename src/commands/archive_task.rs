@@ -0,0 +1,53 @@
+use chrono::{Duration, Local};
+
+use crate::error::{PmError, Result};
+use crate::models::{StatusKind, Task};
+use crate::storage::ProjectStore;
+
+/// Move the given tasks to `archive.json` and return them.
+///
+/// Fails without archiving anything if one of the IDs does not exist.
+pub fn run(project: &str, ids: &[u32]) -> Result<Vec<Task>> {
+    let store = ProjectStore::open(project)?;
+
+    store.update_with_archive(|tasks, archive| {
+        if let Some(missing) = ids.iter().find(|id| !tasks.iter().any(|t| t.id == **id)) {
+            return Err(PmError::TaskNotFound(*missing));
+        }
+
+        Ok(move_to_archive(tasks, archive, |t| ids.contains(&t.id)))
+    })
+}
+
+/// Archive every done task completed more than `days` days ago.
+///
+/// Tasks finished before completion times were recorded are left alone.
+pub fn run_done_older_than(project: &str, days: u32) -> Result<Vec<Task>> {
+    let store = ProjectStore::open(project)?;
+    let workflow = store.load_workflow()?;
+    let cutoff = Local::now() - Duration::days(days.into());
+
+    store.update_with_archive(|tasks, archive| {
+        Ok(move_to_archive(tasks, archive, |t| {
+            workflow.kind(&t.status) == StatusKind::Done
+                && t.completed_at.is_some_and(|at| at < cutoff)
+        }))
+    })
+}
+
+pub(crate) fn move_to_archive(
+    tasks: &mut Vec<Task>,
+    archive: &mut Vec<Task>,
+    selected: impl Fn(&Task) -> bool,
+) -> Vec<Task> {
+    let now = Local::now();
+    let (mut moved, kept): (Vec<Task>, Vec<Task>) = tasks.drain(..).partition(|t| selected(t));
+    *tasks = kept;
+
+    for task in moved.iter_mut() {
+        task.archived_at = Some(now);
+    }
+
+    archive.extend(moved.iter().cloned());
+    moved
+}
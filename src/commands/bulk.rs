@@ -0,0 +1,133 @@
+use chrono::Local;
+
+use crate::config;
+use crate::error::{PmError, Result};
+use crate::models::Task;
+use crate::query::{self, Query};
+use crate::storage::ProjectStore;
+use crate::tags;
+use crate::tree;
+
+use super::archive_task;
+
+/// Changes applied to every matching task; `None` leaves a field as it is.
+#[derive(Debug, Clone, Default)]
+pub struct BulkAction {
+    /// Status name from the project's workflow.
+    pub set_status: Option<String>,
+    pub set_owner: Option<String>,
+    /// Tags to add; tags a task already has are left alone.
+    pub add_tags: Vec<String>,
+    /// Move the tasks to the archive, after the other changes.
+    pub archive: bool,
+    /// Complete tasks even when they have open subtasks.
+    pub force: bool,
+}
+
+impl BulkAction {
+    pub fn is_empty(&self) -> bool {
+        self.set_status.is_none()
+            && self.set_owner.is_none()
+            && self.add_tags.is_empty()
+            && !self.archive
+    }
+}
+
+/// Apply `action` to every active task matching `query` and return the
+/// changed tasks. All tasks are changed in a single update, so it can be
+/// undone in one step; if the change is not allowed for one of them, none
+/// is changed.
+///
+/// With `dry_run` nothing is written and the tasks are returned as they
+/// would be.
+pub fn run(project: &str, query: &str, action: &BulkAction, dry_run: bool) -> Result<Vec<Task>> {
+    if action.is_empty() {
+        return Err(PmError::InvalidInput(
+            "nothing to change (use --set-status, --set-owner, --add-tag or --archive)".into(),
+        ));
+    }
+
+    let store = ProjectStore::open(project)?;
+    let workflow = store.load_workflow()?;
+    let cfg = config::load()?;
+
+    // An empty query would match everything, which is rarely meant
+    let query = Query::parse(query, &workflow, &cfg)?;
+    if query.is_empty() {
+        return Err(PmError::InvalidInput(
+            "no query given (use 'is:open' to change every open task)".into(),
+        ));
+    }
+
+    let status = action
+        .set_status
+        .as_deref()
+        .map(|s| workflow.resolve(s))
+        .transpose()?;
+    let new_tags = action
+        .add_tags
+        .iter()
+        .map(|t| tags::normalize(t))
+        .collect::<Result<Vec<String>>>()?;
+    let user = config::current_user();
+
+    let apply = |tasks: &mut Vec<Task>, archive: &mut Vec<Task>| {
+        let mut all = tasks.clone();
+        all.extend(archive.iter().cloned());
+        let ctx = query::Context {
+            workflow: &workflow,
+            tasks: &all,
+            user: &user,
+            today: Local::now().date_naive(),
+        };
+
+        let ids: Vec<u32> = tasks
+            .iter()
+            .filter(|t| query.matches(t, &ctx))
+            .map(|t| t.id)
+            .collect();
+
+        for task in tasks.iter_mut().filter(|t| ids.contains(&t.id)) {
+            if let Some(status) = &status {
+                workflow.check_transition(&task.status, status)?;
+                task.status = status.clone();
+            }
+            if let Some(owner) = &action.set_owner {
+                task.owner = owner.clone();
+            }
+            for tag in &new_tags {
+                if !task.tags.contains(tag) {
+                    task.tags.push(tag.clone());
+                }
+            }
+        }
+
+        // Checked once all are changed, so a parent can be completed
+        // together with its subtasks
+        if let Some(status) = status.as_ref().filter(|_| !action.force) {
+            for id in &ids {
+                tree::check_subtasks_closed(tasks, &workflow, *id, status)?;
+            }
+        }
+
+        if action.archive {
+            return Ok(archive_task::move_to_archive(tasks, archive, |t| {
+                ids.contains(&t.id)
+            }));
+        }
+
+        Ok(tasks
+            .iter()
+            .filter(|t| ids.contains(&t.id))
+            .cloned()
+            .collect())
+    };
+
+    if dry_run {
+        let mut tasks = store.load_tasks()?;
+        let mut archive = store.load_archive()?;
+        return apply(&mut tasks, &mut archive);
+    }
+
+    store.update_with_archive(apply)
+}
@@ -1,34 +1,8 @@
-use chrono::Local;
-use dirs::home_dir;
-use std::fs;
-
+use crate::error::Result;
 use crate::models::Project;
+use crate::storage::ProjectStore;
 
-pub fn run(name: &str) {
-    let base = home_dir().unwrap().join(".pmcli");
-    fs::create_dir_all(&base).ok();
-
-    let project = base.join(name);
-    if project.exists() {
-        println!("❌ Project already exists");
-        return;
-    }
-
-    fs::create_dir_all(&project).unwrap();
-
-    let meta = Project {
-        name: name.to_string(),
-        created_at: Local::now(), // ✅ FIXED
-    };
-
-    fs::write(
-        project.join("project.json"),
-        serde_json::to_string_pretty(&meta).unwrap(),
-    )
-    .unwrap();
-
-    fs::write(project.join("tasks.json"), "[]").unwrap();
-    fs::write(project.join("notes.md"), "").unwrap();
-
-    println!("✅ Project '{}' created", name);
+/// Create a project and return its metadata.
+pub fn run(name: &str) -> Result<Project> {
+    Ok(ProjectStore::create(name)?.load_project()?)
 }
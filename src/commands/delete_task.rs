@@ -0,0 +1,36 @@
+use crate::error::{PmError, Result};
+use crate::models::Task;
+use crate::storage::ProjectStore;
+
+/// Permanently remove a task, active or archived, and return it.
+///
+/// Its ID is not handed out again. Its subtasks move up to its parent and
+/// tasks depending on it no longer do.
+pub fn run(project: &str, id: u32) -> Result<Task> {
+    let store = ProjectStore::open(project)?;
+
+    store.update_with_archive(|tasks, archive| {
+        let removed = if let Some(pos) = tasks.iter().position(|t| t.id == id) {
+            tasks.remove(pos)
+        } else {
+            match archive.iter().position(|t| t.id == id) {
+                Some(pos) => archive.remove(pos),
+                None => return Err(PmError::TaskNotFound(id)),
+            }
+        };
+
+        for child in tasks
+            .iter_mut()
+            .chain(archive.iter_mut())
+            .filter(|t| t.parent == Some(id))
+        {
+            child.parent = removed.parent;
+        }
+
+        for task in tasks.iter_mut().chain(archive.iter_mut()) {
+            task.depends_on.retain(|d| *d != id);
+        }
+
+        Ok(removed)
+    })
+}
@@ -1,32 +1,34 @@
-use dirs::home_dir;
-use std::fs;
+use crate::error::{PmError, Result};
+use crate::models::{Status, StatusKind, Task};
+use crate::storage::ProjectStore;
+use crate::tree;
 
-use crate::models::{Status, Task};
+/// Mark a task as done and return the updated task.
+///
+/// With a custom workflow the task moves to its first done status. Tasks
+/// with open subtasks are refused unless `force` is set.
+pub fn run(project: &str, id: u32, force: bool) -> Result<Task> {
+    let store = ProjectStore::open(project)?;
+    let workflow = store.load_workflow()?;
 
-pub fn run(project: &str, id: u32) {
-    let path = home_dir()
-        .unwrap()
-        .join(".pmcli")
-        .join(project)
-        .join("tasks.json");
+    let done = workflow
+        .first_of_kind(StatusKind::Done)
+        .cloned()
+        .unwrap_or(Status::Done);
 
-    let mut tasks: Vec<Task> = serde_json::from_str(&fs::read_to_string(&path).unwrap()).unwrap();
-
-    let mut found = false;
-
-    for task in tasks.iter_mut() {
-        if task.id == id {
-            task.status = Status::Done;
-            found = true;
-            break;
+    store.update_tasks(|tasks| {
+        if !force {
+            tree::check_subtasks_closed(tasks, &workflow, id, &done)?;
         }
-    }
 
-    if !found {
-        println!("❌ Task with id {} not found", id);
-        return;
-    }
+        let task = tasks
+            .iter_mut()
+            .find(|t| t.id == id)
+            .ok_or(PmError::TaskNotFound(id))?;
+
+        workflow.check_transition(&task.status, &done)?;
 
-    fs::write(&path, serde_json::to_string_pretty(&tasks).unwrap()).unwrap();
-    println!("✅ Task marked as DONE");
+        task.status = done;
+        Ok(task.clone())
+    })
 }
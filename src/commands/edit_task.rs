@@ -0,0 +1,341 @@
+use std::env;
+use std::fs::{self, File, OpenOptions};
+use std::io::Write;
+use std::path::{Path, PathBuf};
+use std::process::Command;
+
+use crate::config;
+use crate::error::{PmError, Result};
+use crate::models::{Priority, Task};
+use crate::storage::ProjectStore;
+use crate::tags;
+use crate::tree;
+
+use super::link;
+
+/// Field changes for a task; `None` leaves the field as it is.
+#[derive(Debug, Clone, Default)]
+pub struct TaskEdit {
+    pub description: Option<String>,
+    pub priority: Option<String>,
+    /// Deadline in the configured `date_format`.
+    pub deadline: Option<String>,
+    pub owner: Option<String>,
+    pub clear_deadline: bool,
+}
+
+impl TaskEdit {
+    pub fn is_empty(&self) -> bool {
+        self.description.is_none()
+            && self.priority.is_none()
+            && self.deadline.is_none()
+            && self.owner.is_none()
+            && !self.clear_deadline
+    }
+}
+
+/// Apply `edit` to task `id` and return the updated task.
+pub fn run(project: &str, id: u32, edit: &TaskEdit) -> Result<Task> {
+    if edit.is_empty() {
+        return Err(PmError::InvalidInput("nothing to change".into()));
+    }
+
+    let store = ProjectStore::open(project)?;
+    let cfg = config::load()?;
+
+    let deadline = edit
+        .deadline
+        .as_deref()
+        .map(|d| config::parse_date(&cfg, d))
+        .transpose()?;
+
+    let priority: Option<Priority> = edit
+        .priority
+        .as_deref()
+        .map(|p| p.parse().map_err(PmError::InvalidInput))
+        .transpose()?;
+
+    if let Some(desc) = &edit.description {
+        validate_description(desc)?;
+    }
+
+    store.update_tasks(|tasks| {
+        let task = find(tasks, id)?;
+
+        if let Some(desc) = &edit.description {
+            task.description = desc.clone();
+        }
+        if let Some(priority) = priority {
+            task.priority = priority;
+        }
+        if edit.clear_deadline {
+            task.deadline = None;
+        }
+        if deadline.is_some() {
+            task.deadline = deadline;
+        }
+        if let Some(owner) = &edit.owner {
+            task.owner = owner.clone();
+        }
+
+        Ok(task.clone())
+    })
+}
+
+/// Open task `id` as TOML in `$VISUAL`/`$EDITOR` and save the result.
+///
+/// Returns `None` when the task was left unchanged. Only fields changed in
+/// the editor are written back, so edits made meanwhile by other pmcli
+/// processes to other fields survive. Status, parent, dependency and tag
+/// changes are checked like `set-status`, `link` and `tag` do; completing a
+/// task with open subtasks needs `force`. Timestamps cannot be edited.
+///
+/// The edited file is only removed once the task is saved. When saving
+/// fails, the error names the file so the changes can be recovered.
+pub fn run_editor(project: &str, id: u32, force: bool) -> Result<Option<Task>> {
+    let store = ProjectStore::open(project)?;
+
+    let original = store
+        .load_tasks()?
+        .into_iter()
+        .find(|t| t.id == id)
+        .ok_or(PmError::TaskNotFound(id))?;
+
+    let body = toml::to_string_pretty(&original)
+        .map_err(|e| PmError::InvalidInput(format!("cannot render task as TOML: {}", e)))?;
+
+    let (path, mut file) = create_temp_file(project, id)?;
+    let written = write!(
+        file,
+        "# Editing task #{} of '{}'. Save and quit to apply.\n\
+         # Dates use YYYY-MM-DD; remove the deadline line to clear it.\n\n{}",
+        id, project, body
+    );
+    drop(file);
+
+    let edited = written
+        .map_err(PmError::from)
+        .and_then(|_| open_editor(&path))
+        .and_then(|_| Ok(fs::read_to_string(&path)?));
+    let edited = match edited {
+        Ok(edited) => edited,
+        Err(e) => {
+            fs::remove_file(&path).ok();
+            return Err(e);
+        }
+    };
+
+    match save(&store, &original, &edited, force) {
+        Ok(task) => {
+            fs::remove_file(&path).ok();
+            Ok(task)
+        }
+        Err(e) => Err(PmError::EditKept {
+            path,
+            source: Box::new(e),
+        }),
+    }
+}
+
+/// Check the TOML written in the editor against `original` and save the
+/// fields that changed.
+fn save(store: &ProjectStore, original: &Task, edited: &str, force: bool) -> Result<Option<Task>> {
+    let id = original.id;
+    check_priority(edited)?;
+
+    let edited: Task = toml::from_str(edited)
+        .map_err(|e| PmError::InvalidInput(format!("invalid task TOML: {}", e)))?;
+
+    if edited.id != id {
+        return Err(PmError::InvalidInput("task id cannot be changed".into()));
+    }
+    validate_description(&edited.description)?;
+
+    check_read_only(original, &edited)?;
+
+    let mut edited = edited;
+    let mut edited_tags = Vec::new();
+    for tag in &edited.tags {
+        let tag = tags::normalize(tag)?;
+        if !edited_tags.contains(&tag) {
+            edited_tags.push(tag);
+        }
+    }
+    edited.tags = edited_tags;
+
+    let workflow = store.load_workflow()?;
+    if edited.status != original.status {
+        workflow.check_transition(&original.status, &edited.status)?;
+    }
+
+    store.update_with_archive(|tasks, archive| {
+        if edited.status != original.status && !force {
+            tree::check_subtasks_closed(tasks, &workflow, id, &edited.status)?;
+        }
+        if edited.parent != original.parent {
+            if let Some(parent) = edited.parent {
+                check_parent(tasks, id, parent)?;
+            }
+        }
+        if edited.depends_on != original.depends_on {
+            let all: Vec<Task> = tasks.iter().chain(archive.iter()).cloned().collect();
+            for dependency in &edited.depends_on {
+                if !original.depends_on.contains(dependency) {
+                    link::check(&all, id, *dependency)?;
+                }
+            }
+        }
+
+        let task = find(tasks, id)?;
+        let changed = merge_changes(task, original, &edited);
+        Ok(changed.then(|| task.clone()))
+    })
+}
+
+/// Refuse moving task `id` under `parent` when `parent` does not exist or
+/// is `id` itself or one of its subtasks.
+fn check_parent(tasks: &[Task], id: u32, parent: u32) -> Result<()> {
+    if !tasks.iter().any(|t| t.id == parent) {
+        return Err(PmError::TaskNotFound(parent));
+    }
+    if parent == id || tree::descendants(tasks, id).iter().any(|t| t.id == parent) {
+        return Err(PmError::InvalidInput(format!(
+            "task {} cannot be a subtask of its own subtask {}",
+            id, parent
+        )));
+    }
+    Ok(())
+}
+
+/// Fields pmcli maintains itself; changing them in the editor is an error
+/// rather than silently dropped.
+fn check_read_only(original: &Task, edited: &Task) -> Result<()> {
+    let changed = [
+        ("created_at", original.created_at != edited.created_at),
+        ("updated_at", original.updated_at != edited.updated_at),
+        ("completed_at", original.completed_at != edited.completed_at),
+        ("archived_at", original.archived_at != edited.archived_at),
+        ("uuid", original.uuid != edited.uuid),
+    ];
+
+    match changed.iter().find(|(_, changed)| *changed) {
+        Some((field, _)) => Err(PmError::InvalidInput(format!(
+            "{} cannot be changed in the editor",
+            field
+        ))),
+        None => Ok(()),
+    }
+}
+
+/// Copy every field that differs between `original` and `edited` onto
+/// `task`. Returns whether anything was copied.
+fn merge_changes(task: &mut Task, original: &Task, edited: &Task) -> bool {
+    let mut changed = false;
+
+    if edited.description != original.description {
+        task.description = edited.description.clone();
+        changed = true;
+    }
+    if edited.status != original.status {
+        task.status = edited.status.clone();
+        changed = true;
+    }
+    if edited.priority != original.priority {
+        task.priority = edited.priority;
+        changed = true;
+    }
+    if edited.deadline != original.deadline {
+        task.deadline = edited.deadline;
+        changed = true;
+    }
+    if edited.owner != original.owner {
+        task.owner = edited.owner.clone();
+        changed = true;
+    }
+    if edited.parent != original.parent {
+        task.parent = edited.parent;
+        changed = true;
+    }
+    if edited.depends_on != original.depends_on {
+        task.depends_on = edited.depends_on.clone();
+        changed = true;
+    }
+    if edited.tags != original.tags {
+        task.tags = edited.tags.clone();
+        changed = true;
+    }
+
+    changed
+}
+
+/// Create the file to edit under a random name with `create_new`, so a file
+/// or symlink planted in a shared temp directory is never written through.
+fn create_temp_file(project: &str, id: u32) -> Result<(PathBuf, File)> {
+    let path = env::temp_dir().join(format!(
+        "pmcli-{}-{}-{:016x}.toml",
+        project,
+        id,
+        rand::random::<u64>()
+    ));
+
+    let mut options = OpenOptions::new();
+    options.write(true).create_new(true);
+    #[cfg(unix)]
+    std::os::unix::fs::OpenOptionsExt::mode(&mut options, 0o600);
+
+    let file = options.open(&path)?;
+    Ok((path, file))
+}
+
+fn open_editor(path: &Path) -> Result<()> {
+    let editor = env::var("VISUAL")
+        .or_else(|_| env::var("EDITOR"))
+        .unwrap_or_else(|_| "vi".to_string());
+
+    // Allow editors configured with arguments, e.g. `code --wait`
+    let mut parts = editor.split_whitespace();
+    let program = parts.next().unwrap_or("vi");
+
+    let status = Command::new(program)
+        .args(parts)
+        .arg(path)
+        .status()
+        .map_err(|e| PmError::InvalidInput(format!("cannot start editor '{}': {}", editor, e)))?;
+
+    if !status.success() {
+        return Err(PmError::InvalidInput(format!(
+            "editor '{}' exited with {}, task left unchanged",
+            editor, status
+        )));
+    }
+
+    Ok(())
+}
+
+/// `Task` loads unknown priorities as medium; a typo made in the editor
+/// should be reported instead.
+fn check_priority(toml_text: &str) -> Result<()> {
+    let table: toml::Table = toml::from_str(toml_text)
+        .map_err(|e| PmError::InvalidInput(format!("invalid task TOML: {}", e)))?;
+
+    match table.get("priority") {
+        Some(toml::Value::String(p)) => p.parse::<Priority>().map(|_| ()),
+        Some(toml::Value::Integer(level)) => level.to_string().parse::<Priority>().map(|_| ()),
+        _ => Ok(()),
+    }
+    .map_err(PmError::InvalidInput)
+}
+
+fn find(tasks: &mut [Task], id: u32) -> Result<&mut Task> {
+    tasks
+        .iter_mut()
+        .find(|t| t.id == id)
+        .ok_or(PmError::TaskNotFound(id))
+}
+
+fn validate_description(desc: &str) -> Result<()> {
+    if desc.trim().is_empty() {
+        return Err(PmError::InvalidInput("description cannot be empty".into()));
+    }
+    Ok(())
+}
@@ -1,22 +1,256 @@
-use crate::models::Task;
-use dirs::home_dir;
-use std::fs;
+use std::str::FromStr;
 
-pub fn run(project: &str) {
-    let base = home_dir().unwrap().join(".pmcli").join(project);
-    let tasks: Vec<Task> =
-        serde_json::from_str(&fs::read_to_string(base.join("tasks.json")).unwrap()).unwrap();
+use chrono::{DateTime, Local};
 
-    let mut csv = "id,status,priority,deadline,description\n".to_string();
+use crate::config;
+use crate::csv;
+use crate::error::{PmError, Result};
+use crate::ical;
+use crate::models::{Config, Task};
+use crate::report::Report;
+use crate::storage::ProjectStore;
+use crate::taskwarrior;
 
-    for t in tasks {
-        let deadline = t.deadline.map(|d| d.to_string()).unwrap_or("".into());
-        csv.push_str(&format!(
-            "{},{:?},{},{},\"{}\"\n",
-            t.id, t.status, t.priority, deadline, t.description
-        ));
+use super::tasks::{self, TaskQuery};
+
+/// A column of the CSV export.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Column {
+    Id,
+    Description,
+    Status,
+    Priority,
+    Deadline,
+    Owner,
+    Parent,
+    DependsOn,
+    Tags,
+    CreatedAt,
+    UpdatedAt,
+    CompletedAt,
+    ArchivedAt,
+    /// UUID of a task imported from Taskwarrior.
+    Uuid,
+}
+
+impl Column {
+    /// Every column, in the default order.
+    pub const ALL: [Column; 14] = [
+        Column::Id,
+        Column::Description,
+        Column::Status,
+        Column::Priority,
+        Column::Deadline,
+        Column::Owner,
+        Column::Parent,
+        Column::DependsOn,
+        Column::Tags,
+        Column::CreatedAt,
+        Column::UpdatedAt,
+        Column::CompletedAt,
+        Column::ArchivedAt,
+        Column::Uuid,
+    ];
+
+    /// Header name, as accepted by `--columns`.
+    pub fn name(self) -> &'static str {
+        match self {
+            Column::Id => "id",
+            Column::Description => "description",
+            Column::Status => "status",
+            Column::Priority => "priority",
+            Column::Deadline => "deadline",
+            Column::Owner => "owner",
+            Column::Parent => "parent",
+            Column::DependsOn => "depends_on",
+            Column::Tags => "tags",
+            Column::CreatedAt => "created_at",
+            Column::UpdatedAt => "updated_at",
+            Column::CompletedAt => "completed_at",
+            Column::ArchivedAt => "archived_at",
+            Column::Uuid => "uuid",
+        }
+    }
+
+    /// The field of `task` in this column.
+    ///
+    /// Deadlines use the configured `date_format`, timestamps RFC 3339, and
+    /// lists (dependencies, tags) are comma-separated.
+    pub fn value(self, task: &Task, cfg: &Config) -> String {
+        let time = |t: Option<DateTime<Local>>| t.map(|t| t.to_rfc3339()).unwrap_or_default();
+
+        match self {
+            Column::Id => task.id.to_string(),
+            Column::Description => task.description.clone(),
+            Column::Status => task.status.to_string(),
+            Column::Priority => task.priority.to_string(),
+            Column::Deadline => task
+                .deadline
+                .map(|d| config::format_date(cfg, d))
+                .unwrap_or_default(),
+            Column::Owner => task.owner.clone(),
+            Column::Parent => task.parent.map(|p| p.to_string()).unwrap_or_default(),
+            Column::DependsOn => task
+                .depends_on
+                .iter()
+                .map(|id| id.to_string())
+                .collect::<Vec<_>>()
+                .join(","),
+            Column::Tags => task.tags.join(","),
+            Column::CreatedAt => time(task.created_at),
+            Column::UpdatedAt => time(task.updated_at),
+            Column::CompletedAt => time(task.completed_at),
+            Column::ArchivedAt => time(task.archived_at),
+            Column::Uuid => task.uuid.clone().unwrap_or_default(),
+        }
+    }
+}
+
+impl FromStr for Column {
+    type Err = String;
+
+    fn from_str(s: &str) -> std::result::Result<Self, Self::Err> {
+        Column::ALL
+            .into_iter()
+            .find(|c| c.name().eq_ignore_ascii_case(s.trim()))
+            .ok_or_else(|| {
+                let names: Vec<&str> = Column::ALL.iter().map(|c| c.name()).collect();
+                format!("unknown column '{}' (expected {})", s, names.join(", "))
+            })
+    }
+}
+
+/// Output format of an export.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum ExportFormat {
+    /// One row per task, see [`Column`].
+    #[default]
+    Csv,
+    /// Project report, see [`Report`].
+    Markdown,
+    /// Project report as a self-contained HTML page.
+    Html,
+    /// iCalendar with the deadlines of tasks, see [`ical::calendar`].
+    Ics,
+    /// The whole project for `pmcli import`, see [`crate::bundle`]. Ignores
+    /// the query.
+    Bundle,
+    /// Taskwarrior JSON for `task import`, see [`taskwarrior::export`].
+    Taskwarrior,
+}
+
+impl FromStr for ExportFormat {
+    type Err = String;
+
+    fn from_str(s: &str) -> std::result::Result<Self, Self::Err> {
+        match s {
+            "csv" => Ok(ExportFormat::Csv),
+            "markdown" | "md" => Ok(ExportFormat::Markdown),
+            "html" => Ok(ExportFormat::Html),
+            "ics" | "ical" => Ok(ExportFormat::Ics),
+            "bundle" => Ok(ExportFormat::Bundle),
+            "taskwarrior" | "tw" => Ok(ExportFormat::Taskwarrior),
+            _ => Err(format!(
+                "unknown format '{}' (expected csv, markdown, html, ics, bundle or taskwarrior)",
+                s
+            )),
+        }
+    }
+}
+
+/// What to export and how.
+#[derive(Debug, Clone)]
+pub struct ExportOptions {
+    pub format: ExportFormat,
+    /// Filter expression selecting the tasks, see [`crate::query`].
+    pub query: Option<String>,
+    pub include_archived: bool,
+    /// CSV columns to write, in this order; empty for all of [`Column::ALL`].
+    pub columns: Vec<Column>,
+    /// CSV field separator.
+    pub delimiter: char,
+}
+
+impl Default for ExportOptions {
+    fn default() -> Self {
+        ExportOptions {
+            format: ExportFormat::Csv,
+            query: None,
+            include_archived: false,
+            columns: Vec::new(),
+            delimiter: ',',
+        }
+    }
+}
+
+/// Tasks of a project matching the options' query, in the requested format.
+pub fn run(project: &str, options: &ExportOptions) -> Result<String> {
+    let query = TaskQuery {
+        query: options.query.clone(),
+        include_archived: options.include_archived,
+        ..Default::default()
+    };
+    let tasks = || tasks::run(project, &query);
+    let cfg = config::load()?;
+
+    match options.format {
+        ExportFormat::Csv => to_csv(&tasks()?, options, &cfg),
+        ExportFormat::Markdown => Ok(report(project, tasks()?)?.markdown(&cfg)),
+        ExportFormat::Html => Ok(report(project, tasks()?)?.html(&cfg)),
+        ExportFormat::Ics => {
+            let workflow = ProjectStore::open(project)?.load_workflow()?;
+            Ok(ical::calendar(project, &tasks()?, &workflow))
+        }
+        ExportFormat::Bundle => {
+            let mut bundle = ProjectStore::open(project)?.load_bundle()?;
+            bundle.assign_uuids();
+            bundle.to_json()
+        }
+        ExportFormat::Taskwarrior => {
+            let store = ProjectStore::open(project)?;
+            let mut all = store.load_tasks()?;
+            all.extend(store.load_archive()?);
+            taskwarrior::export(project, &tasks()?, &all, &store.load_workflow()?)
+        }
+    }
+}
+
+/// CSV with a header line, see [`Column::value`] for how fields are
+/// written.
+fn to_csv(tasks: &[Task], options: &ExportOptions, cfg: &Config) -> Result<String> {
+    if matches!(options.delimiter, '"' | '\r' | '\n') {
+        return Err(PmError::InvalidInput(format!(
+            "{:?} cannot be used as delimiter",
+            options.delimiter
+        )));
     }
 
-    fs::write(base.join("tasks.csv"), csv).unwrap();
-    println!("📤 Exported to tasks.csv");
+    let columns = if options.columns.is_empty() {
+        Column::ALL.to_vec()
+    } else {
+        options.columns.clone()
+    };
+
+    let mut out = String::new();
+    let header: Vec<&str> = columns.iter().map(|c| c.name()).collect();
+    csv::write_record(&mut out, &header, options.delimiter);
+
+    for task in tasks {
+        let fields: Vec<String> = columns.iter().map(|c| c.value(task, cfg)).collect();
+        csv::write_record(&mut out, &fields, options.delimiter);
+    }
+
+    Ok(out)
+}
+
+fn report(project: &str, tasks: Vec<Task>) -> Result<Report> {
+    let store = ProjectStore::open(project)?;
+
+    Ok(Report::build(
+        store.load_project()?,
+        tasks,
+        &store.load_workflow()?,
+        store.load_notes()?,
+        Local::now().date_naive(),
+    ))
 }
@@ -1,35 +1,60 @@
-use dirs::home_dir;
 use git2::{Repository, Signature};
 use std::env;
-use std::path::PathBuf;
+use std::path::{Path, PathBuf};
 use std::process::Command;
 
-fn project_path(project: &str) -> PathBuf {
-    home_dir().unwrap().join(".pmcli").join(project)
+use crate::error::{PmError, Result};
+use crate::storage::ProjectStore;
+
+/// How a push or pull authenticated against the remote.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum SyncAuth {
+    /// Regular git credentials (helpers, SSH keys).
+    Credential,
+    /// `GITHUB_TOKEN` injected into the HTTPS origin URL.
+    Token,
+}
+
+fn project_path(project: &str) -> Result<PathBuf> {
+    Ok(ProjectStore::open(project)?.dir().to_path_buf())
 }
 
-pub fn init(project: &str) {
-    let path = project_path(project);
-    Repository::init(&path).expect("Failed to init git repo");
-    println!("📁 Git repository initialized");
+pub fn init(project: &str) -> Result<()> {
+    let path = project_path(project)?;
+    Repository::init(&path)?;
+    Ok(())
 }
 
-pub fn commit(project: &str, message: &str) {
-    let path = project_path(project);
-    let repo = Repository::open(&path).expect("Not a git repository");
+pub fn commit(project: &str, message: &str) -> Result<()> {
+    let path = project_path(project)?;
+    let repo = Repository::open(&path)
+        .map_err(|_| PmError::Git(format!("'{}' is not a git repository", project)))?;
+
+    // Skip the lock file and in-flight temp files from atomic saves
+    let mut skip_internal = |p: &Path, _: &[u8]| -> i32 {
+        let name = p.file_name().and_then(|n| n.to_str()).unwrap_or("");
+        if name == ".lock" || name.ends_with(".tmp") {
+            1
+        } else {
+            0
+        }
+    };
 
-    let mut index = repo.index().unwrap();
-    index
-        .add_all(["*"].iter(), git2::IndexAddOption::DEFAULT, None)
-        .unwrap();
-    index.write().unwrap();
+    let mut index = repo.index()?;
+    index.add_all(
+        ["*"].iter(),
+        git2::IndexAddOption::DEFAULT,
+        Some(&mut skip_internal),
+    )?;
+    index.write()?;
 
-    let tree_id = index.write_tree().unwrap();
-    let tree = repo.find_tree(tree_id).unwrap();
+    let tree_id = index.write_tree()?;
+    let tree = repo.find_tree(tree_id)?;
 
-    let sig = repo
-        .signature()
-        .unwrap_or(Signature::now("pmcli", "pmcli@local").unwrap());
+    let sig = match repo.signature() {
+        Ok(sig) => sig,
+        Err(_) => Signature::now("pmcli", "pmcli@local")?,
+    };
 
     let parent = repo
         .head()
@@ -39,126 +64,84 @@ pub fn commit(project: &str, message: &str) {
 
     match parent {
         Some(p) => {
-            repo.commit(Some("HEAD"), &sig, &sig, message, &tree, &[&p])
-                .unwrap();
+            repo.commit(Some("HEAD"), &sig, &sig, message, &tree, &[&p])?;
         }
         None => {
-            repo.commit(Some("HEAD"), &sig, &sig, message, &tree, &[])
-                .unwrap();
+            repo.commit(Some("HEAD"), &sig, &sig, message, &tree, &[])?;
         }
     }
 
-    println!("✅ Commit created");
+    Ok(())
 }
 
-pub fn push(project: &str) {
-    let path = project_path(project);
+pub fn push(project: &str) -> Result<SyncAuth> {
+    let path = project_path(project)?;
 
     // 1️⃣ NORMAL PUSH (USE GIT CREDENTIAL)
-    let normal = Command::new("git")
-        .arg("push")
-        .current_dir(&path)
-        .status()
-        .expect("Failed to run git push");
-
-    if normal.success() {
-        println!("⬆️  Push successful (git credential)");
-        return;
+    if run_git(&path, &["push"])? {
+        return Ok(SyncAuth::Credential);
     }
 
     // 2️⃣ FALLBACK TOKEN
-    let token = match env::var("GITHUB_TOKEN") {
-        Ok(t) => t,
-        Err(_) => {
-            println!("❌ Push failed and no GITHUB_TOKEN set");
-            return;
-        }
-    };
+    let auth_remote = token_remote(&path, "push")?;
 
-    let output = Command::new("git")
-        .arg("remote")
-        .arg("get-url")
-        .arg("origin")
-        .current_dir(&path)
-        .output()
-        .expect("Failed to read git remote");
+    if run_git(&path, &["push", &auth_remote])? {
+        Ok(SyncAuth::Token)
+    } else {
+        Err(PmError::Git("push failed even with token".into()))
+    }
+}
 
-    let remote = String::from_utf8(output.stdout).unwrap();
-    let remote = remote.trim();
+pub fn pull(project: &str) -> Result<SyncAuth> {
+    let path = project_path(project)?;
 
-    if !remote.starts_with("https://") {
-        println!("❌ Remote is not HTTPS (cannot inject token)");
-        return;
+    // 1️⃣ NORMAL PULL
+    if run_git(&path, &["pull"])? {
+        return Ok(SyncAuth::Credential);
     }
 
-    let auth_remote = remote.replacen("https://", &format!("https://{}@", token), 1);
-
-    let status = Command::new("git")
-        .arg("push")
-        .arg(&auth_remote)
-        .current_dir(&path)
-        .status()
-        .expect("Failed to run git push (token)");
+    // 2️⃣ FALLBACK TOKEN
+    let auth_remote = token_remote(&path, "pull")?;
 
-    if status.success() {
-        println!("⬆️  Push successful (token fallback)");
+    if run_git(&path, &["pull", &auth_remote])? {
+        Ok(SyncAuth::Token)
     } else {
-        println!("❌ Push failed even with token");
+        Err(PmError::Git("pull failed even with token".into()))
     }
 }
 
-pub fn pull(project: &str) {
-    let path = project_path(project);
-
-    // 1️⃣ NORMAL PULL
-    let normal = Command::new("git")
-        .arg("pull")
-        .current_dir(&path)
+/// Run the `git` binary in `dir`, returning whether it succeeded.
+fn run_git(dir: &Path, args: &[&str]) -> Result<bool> {
+    let status = Command::new("git")
+        .args(args)
+        .current_dir(dir)
         .status()
-        .expect("Failed to run git pull");
+        .map_err(|e| PmError::Git(format!("failed to run git {}: {}", args[0], e)))?;
 
-    if normal.success() {
-        println!("⬇️  Pull successful (git credential)");
-        return;
-    }
+    Ok(status.success())
+}
 
-    // 2️⃣ FALLBACK TOKEN
-    let token = match env::var("GITHUB_TOKEN") {
-        Ok(t) => t,
-        Err(_) => {
-            println!("❌ Pull failed and no GITHUB_TOKEN set");
-            return;
-        }
-    };
+/// Origin URL with `GITHUB_TOKEN` injected, for when credential helpers fail.
+fn token_remote(dir: &Path, action: &str) -> Result<String> {
+    let token = env::var("GITHUB_TOKEN")
+        .map_err(|_| PmError::Git(format!("{} failed and no GITHUB_TOKEN set", action)))?;
 
     let output = Command::new("git")
         .arg("remote")
         .arg("get-url")
         .arg("origin")
-        .current_dir(&path)
+        .current_dir(dir)
         .output()
-        .expect("Failed to read git remote");
+        .map_err(|e| PmError::Git(format!("failed to read git remote: {}", e)))?;
 
-    let remote = String::from_utf8(output.stdout).unwrap();
+    let remote = String::from_utf8_lossy(&output.stdout);
     let remote = remote.trim();
 
     if !remote.starts_with("https://") {
-        println!("❌ Remote is not HTTPS (cannot inject token)");
-        return;
+        return Err(PmError::Git(
+            "remote is not HTTPS (cannot inject token)".into(),
+        ));
     }
 
-    let auth_remote = remote.replacen("https://", &format!("https://{}@", token), 1);
-
-    let status = Command::new("git")
-        .arg("pull")
-        .arg(&auth_remote)
-        .current_dir(&path)
-        .status()
-        .expect("Failed to run git pull (token)");
-
-    if status.success() {
-        println!("⬇️  Pull successful (token fallback)");
-    } else {
-        println!("❌ Pull failed even with token");
-    }
+    Ok(remote.replacen("https://", &format!("https://{}@", token), 1))
 }
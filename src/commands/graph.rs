@@ -0,0 +1,59 @@
+use crate::deps;
+use crate::error::Result;
+use crate::models::StatusKind;
+use crate::storage::ProjectStore;
+
+/// The project's dependency graph in Graphviz DOT format.
+///
+/// Every active task is a node; an edge `a -> b` means `b` depends on `a`.
+/// Render it with e.g. `pmcli graph web | dot -Tsvg > web.svg`.
+pub fn run(project: &str) -> Result<String> {
+    let store = ProjectStore::open(project)?;
+    let workflow = store.load_workflow()?;
+    let tasks = store.load_tasks()?;
+
+    let mut all = tasks.clone();
+    all.extend(store.load_archive()?);
+
+    let mut dot = format!("digraph \"{}\" {{\n", escape(project));
+    dot.push_str("    rankdir=LR;\n    node [shape=box, style=rounded];\n");
+
+    for task in &tasks {
+        let color = if !deps::waiting_on(&all, &workflow, task).is_empty() {
+            "red"
+        } else {
+            match workflow.kind(&task.status) {
+                StatusKind::Todo => "black",
+                StatusKind::Active => "orange",
+                StatusKind::Blocked => "red",
+                StatusKind::Done => "darkgreen",
+                StatusKind::Cancelled => "gray",
+            }
+        };
+
+        dot.push_str(&format!(
+            "    t{} [label=\"#{} {}\\n{}\", color={}];\n",
+            task.id,
+            task.id,
+            escape(&task.description),
+            escape(&task.status.to_string()),
+            color
+        ));
+    }
+
+    for task in &tasks {
+        for dep in &task.depends_on {
+            // Dependencies that are archived or deleted have no node
+            if tasks.iter().any(|t| t.id == *dep) {
+                dot.push_str(&format!("    t{} -> t{};\n", dep, task.id));
+            }
+        }
+    }
+
+    dot.push_str("}\n");
+    Ok(dot)
+}
+
+fn escape(s: &str) -> String {
+    s.replace('\\', "\\\\").replace('"', "\\\"")
+}
@@ -0,0 +1,650 @@
+use std::collections::{BTreeMap, HashMap};
+use std::fs;
+use std::ops::Range;
+use std::path::Path;
+use std::str::FromStr;
+
+use chrono::Local;
+
+use crate::bundle::Bundle;
+use crate::error::{PmError, Result};
+use crate::models::{Project, Status, Task};
+use crate::storage::{ProjectStore, StorageError};
+use crate::taskwarrior::{self, TwTask};
+use crate::workflow::Workflow;
+use crate::{config, tree};
+
+use super::link;
+
+/// Format of the imported file.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum ImportSource {
+    /// Written by `export --format bundle`, see [`crate::bundle`].
+    #[default]
+    Bundle,
+    /// Output of Taskwarrior's `task export`, see [`crate::taskwarrior`].
+    Taskwarrior,
+}
+
+impl FromStr for ImportSource {
+    type Err = String;
+
+    fn from_str(s: &str) -> std::result::Result<Self, Self::Err> {
+        match s {
+            "bundle" => Ok(ImportSource::Bundle),
+            "taskwarrior" | "tw" => Ok(ImportSource::Taskwarrior),
+            _ => Err(format!(
+                "unknown source '{}' (expected bundle or taskwarrior)",
+                s
+            )),
+        }
+    }
+}
+
+/// Where and how to import.
+#[derive(Debug, Clone, Default)]
+pub struct ImportOptions {
+    pub from: ImportSource,
+    /// Project to import into; the bundle's project name, or each
+    /// Taskwarrior task's project, when `None`.
+    pub name: Option<String>,
+    /// Replace an existing project with the bundle.
+    pub force: bool,
+    /// Add the bundle's tasks and notes to an existing project.
+    pub merge: bool,
+}
+
+/// Outcome of an import into one project.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Imported {
+    pub project: String,
+    /// Active and archived tasks added to the project.
+    pub added: usize,
+    /// Existing tasks a merge updated.
+    pub updated: usize,
+    /// Whether the tasks were merged into an existing project.
+    pub merged: bool,
+}
+
+/// Import a bundle written by `export --format bundle`, or a Taskwarrior
+/// export with one project per Taskwarrior project.
+///
+/// A new project gets the bundle's contents as they are. An existing one is
+/// only touched with `force`, which replaces it, or `merge`, which adds the
+/// bundle's tasks under fresh IDs and appends its notes, keeping the
+/// project's own workflow and history. Merged tasks with the UUID of a task
+/// already in the project update that task instead, so a bundle or a
+/// Taskwarrior export can be merged again.
+///
+/// Existing targets are checked, and merges tried on a copy of the
+/// project, before anything is written; projects created by an import that
+/// fails are removed again.
+pub fn run(path: &Path, options: &ImportOptions) -> Result<Vec<Imported>> {
+    let data = fs::read_to_string(path).map_err(|source| StorageError::Io {
+        path: path.to_path_buf(),
+        source,
+    })?;
+
+    // Each bundle with the projects its tasks were exported from
+    let bundles = match options.from {
+        ImportSource::Bundle => {
+            let mut bundle = Bundle::parse(&data)?;
+            // Bundles written before tasks carried a UUID
+            bundle.assign_uuids();
+            vec![(bundle, Vec::new())]
+        }
+        ImportSource::Taskwarrior => from_taskwarrior(taskwarrior::parse(&data)?, options)?,
+    };
+
+    // Check every target before touching any, and take out the projects
+    // this import created when a later one fails
+    let mut created = Vec::new();
+    for (bundle, sources) in &bundles {
+        let name = target(bundle, options);
+        match ProjectStore::open(&name) {
+            Ok(_) if !options.merge && !options.force => return Err(exists(&name)),
+            Ok(store) if options.merge => check_merge(&store, bundle, sources)?,
+            Ok(_) => {}
+            Err(_) => created.push(name),
+        }
+    }
+
+    let mut imported = Vec::new();
+    for (bundle, sources) in bundles {
+        match import(bundle, &sources, options) {
+            Ok(project) => imported.push(project),
+            Err(e) => {
+                for project in imported.iter().filter(|i| created.contains(&i.project)) {
+                    if let Ok(store) = ProjectStore::open(&project.project) {
+                        fs::remove_dir_all(store.dir()).ok();
+                    }
+                }
+                return Err(e);
+            }
+        }
+    }
+    Ok(imported)
+}
+
+/// Name of the project `bundle` is imported into.
+fn target(bundle: &Bundle, options: &ImportOptions) -> String {
+    options
+        .name
+        .clone()
+        .unwrap_or_else(|| bundle.project.name.clone())
+}
+
+fn exists(name: &str) -> PmError {
+    PmError::InvalidInput(format!(
+        "project '{}' already exists (use --force to replace it, --merge to add to it \
+         or --as to import under another name)",
+        name
+    ))
+}
+
+fn import(bundle: Bundle, sources: &[String], options: &ImportOptions) -> Result<Imported> {
+    let name = target(&bundle, options);
+    let count = bundle.tasks.len() + bundle.archive.len();
+
+    let ((added, updated), merged) = match ProjectStore::create(&name) {
+        Ok(store) => {
+            if let Err(e) = store.restore_bundle(&bundle) {
+                // Do not leave a half-imported project behind
+                fs::remove_dir_all(store.dir()).ok();
+                return Err(e.into());
+            }
+            ((count, 0), false)
+        }
+        Err(StorageError::ProjectExists(_)) if options.merge => {
+            (merge(&ProjectStore::open(&name)?, bundle, sources)?, true)
+        }
+        Err(StorageError::ProjectExists(_)) if options.force => {
+            ProjectStore::open(&name)?.restore_bundle(&bundle)?;
+            ((count, 0), false)
+        }
+        Err(StorageError::ProjectExists(_)) => return Err(exists(&name)),
+        Err(e) => return Err(e.into()),
+    };
+
+    Ok(Imported {
+        project: name,
+        added,
+        updated,
+        merged,
+    })
+}
+
+/// One bundle per target project, with the Taskwarrior projects of its
+/// tasks. Statuses are mapped with the workflow of the project when it
+/// exists.
+fn from_taskwarrior(
+    tasks: Vec<TwTask>,
+    options: &ImportOptions,
+) -> Result<Vec<(Bundle, Vec<String>)>> {
+    let cfg = config::load()?;
+    let owner = config::current_user();
+
+    let mut projects: BTreeMap<String, Vec<TwTask>> = BTreeMap::new();
+    for task in tasks {
+        let name = match (&options.name, &task.project) {
+            (Some(name), _) | (None, Some(name)) => name.clone(),
+            (None, None) => {
+                return Err(PmError::InvalidInput(format!(
+                    "task '{}' has no project (use --as to import into one)",
+                    task.description
+                )))
+            }
+        };
+        projects.entry(name).or_default().push(task);
+    }
+
+    projects
+        .into_iter()
+        .map(|(name, tw)| {
+            let workflow = match ProjectStore::open(&name) {
+                Ok(store) => store.load_workflow()?,
+                Err(_) => Default::default(),
+            };
+            let mut sources: Vec<String> = tw.iter().filter_map(|t| t.project.clone()).collect();
+            sources.sort();
+            sources.dedup();
+
+            let tasks = taskwarrior::to_tasks(
+                &tw,
+                &workflow,
+                cfg.default_priority.unwrap_or_default(),
+                &owner,
+            )?;
+
+            let project = Project {
+                created_at: tasks
+                    .iter()
+                    .filter_map(|t| t.created_at)
+                    .min()
+                    .unwrap_or_else(Local::now),
+                next_task_id: tasks.len() as u32 + 1,
+                name,
+            };
+            Ok((Bundle::new(project, tasks), sources))
+        })
+        .collect()
+}
+
+/// Add the bundle's tasks to `store` in one undoable update, renumbering
+/// them and their parent and dependency links. Their timestamps are kept.
+/// Returns how many tasks were added and how many updated.
+///
+/// Incoming tasks are matched to existing ones by the UUID they were
+/// imported with, then by the UUID derived for tasks created in pmcli,
+/// under this project's name or one of `sources`, so tasks exported from
+/// a project and imported under another name are still found.
+///
+/// Statuses the project's workflow does not have become its first status
+/// of the same kind, or else its first status. The merge is refused as a
+/// whole when it would leave parent or dependency loops, or move an updated
+/// task to a status its workflow or its open subtasks do not allow.
+fn merge(store: &ProjectStore, bundle: Bundle, sources: &[String]) -> Result<(usize, usize)> {
+    let workflow = store.load_workflow()?;
+    let projects = projects(store, sources);
+
+    store.import_with_archive(|tasks, archive| {
+        let counts = merge_into(
+            tasks,
+            archive,
+            &bundle,
+            &workflow,
+            &projects,
+            |tasks, count| Ok(store.allocate_task_ids(tasks, count)?),
+        )?;
+
+        // Notes are not journaled, so undo only takes the tasks back out
+        if !bundle.notes.trim().is_empty() {
+            let mut notes = store.load_notes()?;
+            if !notes.is_empty() && !notes.ends_with('\n') {
+                notes.push('\n');
+            }
+            notes.push_str(&bundle.notes);
+            store.save_notes(&notes)?;
+        }
+
+        Ok(counts)
+    })
+}
+
+/// Try [`merge`] on a copy of the project's tasks, so a merge that would be
+/// refused is found before any project of the import is written.
+fn check_merge(store: &ProjectStore, bundle: &Bundle, sources: &[String]) -> Result<()> {
+    let workflow = store.load_workflow()?;
+    let mut tasks = store.load_tasks()?;
+    let mut archive = store.load_archive()?;
+    let next = tasks
+        .iter()
+        .chain(&archive)
+        .map(|t| t.id)
+        .max()
+        .unwrap_or(0)
+        + 1;
+
+    merge_into(
+        &mut tasks,
+        &mut archive,
+        bundle,
+        &workflow,
+        &projects(store, sources),
+        |_, count| Ok(next..next.saturating_add(count)),
+    )
+    .map(|_| ())
+}
+
+/// Project names UUIDs of existing tasks may be derived under.
+fn projects<'a>(store: &'a ProjectStore, sources: &'a [String]) -> Vec<&'a str> {
+    std::iter::once(store.name())
+        .chain(sources.iter().map(String::as_str))
+        .collect()
+}
+
+/// Body of [`merge`]: add or update the bundle's tasks in `tasks` and
+/// `archive`, taking IDs for added tasks from `allocate`.
+fn merge_into(
+    tasks: &mut Vec<Task>,
+    archive: &mut Vec<Task>,
+    bundle: &Bundle,
+    workflow: &Workflow,
+    projects: &[&str],
+    allocate: impl FnOnce(&[Task], u32) -> Result<Range<u32>>,
+) -> Result<(usize, usize)> {
+    let source = bundle.workflow.clone().unwrap_or_default();
+    let incoming = |list: &[Task]| -> Vec<Task> {
+        list.iter()
+            .map(|t| Task {
+                status: status_in(workflow, &source, &t.status),
+                ..t.clone()
+            })
+            .collect()
+    };
+    let (bundle_tasks, bundle_archive) = (incoming(&bundle.tasks), incoming(&bundle.archive));
+
+    let mut ids = HashMap::new();
+    let mut updated = Vec::new();
+    let mut added = Vec::new();
+
+    for task in bundle_tasks.iter().chain(&bundle_archive) {
+        let existing = task
+            .uuid
+            .as_deref()
+            .and_then(|uuid| find_uuid(tasks, archive, uuid, projects));
+
+        match existing {
+            // Two UUIDs, e.g. one stored and one derived, for one task
+            Some(existing) if updated.contains(&existing) => {
+                return Err(PmError::InvalidInput(format!(
+                    "more than one imported task matches task {}",
+                    existing
+                )));
+            }
+            Some(existing) => {
+                updated.push(existing);
+                ids.insert(task.id, existing);
+            }
+            None => added.push(task.id),
+        }
+    }
+
+    let before: HashMap<u32, Status> = tasks
+        .iter()
+        .chain(archive.iter())
+        .filter(|t| updated.contains(&t.id))
+        .map(|t| (t.id, t.status.clone()))
+        .collect();
+
+    let counts = (added.len(), updated.len());
+    let new_ids = allocate(tasks, added.len() as u32)?;
+    ids.extend(added.into_iter().zip(new_ids));
+
+    let renumber = |mut task: Task| {
+        task.id = ids[&task.id];
+        task.parent = task.parent.and_then(|p| ids.get(&p).copied());
+        task.depends_on = task
+            .depends_on
+            .iter()
+            .filter_map(|d| ids.get(d).copied())
+            .collect();
+        task
+    };
+
+    for task in bundle_tasks.into_iter().map(&renumber) {
+        if !update(tasks, archive, &updated, &task) {
+            tasks.push(task);
+        }
+    }
+    for task in bundle_archive.into_iter().map(renumber) {
+        if !update(tasks, archive, &updated, &task) {
+            archive.push(task);
+        }
+    }
+
+    let all: Vec<Task> = tasks.iter().chain(archive.iter()).cloned().collect();
+    let merged: Vec<u32> = ids.values().copied().collect();
+    check_merged(&all, workflow, &merged, &before)?;
+
+    Ok(counts)
+}
+
+/// `status` when `workflow` has it, else the first status of `workflow`
+/// with the kind `status` has in `source`, else where new tasks start.
+fn status_in(workflow: &Workflow, source: &Workflow, status: &Status) -> Status {
+    if workflow.contains(status) {
+        return status.clone();
+    }
+
+    workflow
+        .first_of_kind(source.kind(status))
+        .unwrap_or(workflow.initial())
+        .clone()
+}
+
+/// ID of the task imported with `uuid`, or else of a task created in pmcli
+/// whose UUID derived in one of `projects` is `uuid`.
+fn find_uuid(tasks: &[Task], archive: &[Task], uuid: &str, projects: &[&str]) -> Option<u32> {
+    let all = || tasks.iter().chain(archive);
+
+    all()
+        .find(|t| t.uuid.as_deref() == Some(uuid))
+        .or_else(|| {
+            all()
+                .filter(|t| t.uuid.is_none())
+                .find(|t| projects.iter().any(|p| taskwarrior::uuid(p, t) == uuid))
+        })
+        .map(|t| t.id)
+}
+
+/// Refuse merged tasks (`merged`, checked in `all` after the merge) that
+/// sit under their own subtask, wait on themselves, or changed from their
+/// status in `before` in a way the workflow or open subtasks forbid.
+fn check_merged(
+    all: &[Task],
+    workflow: &Workflow,
+    merged: &[u32],
+    before: &HashMap<u32, Status>,
+) -> Result<()> {
+    for task in all.iter().filter(|t| merged.contains(&t.id)) {
+        if let Some(parent) = task.parent {
+            if parent == task.id
+                || tree::descendants(all, task.id)
+                    .iter()
+                    .any(|t| t.id == parent)
+            {
+                return Err(PmError::InvalidInput(format!(
+                    "task {} cannot be a subtask of its own subtask {}",
+                    task.id, parent
+                )));
+            }
+        }
+
+        for dependency in &task.depends_on {
+            link::check(all, task.id, *dependency)?;
+        }
+
+        if let Some(from) = before.get(&task.id) {
+            if *from != task.status {
+                workflow.check_transition(from, &task.status)?;
+                tree::check_subtasks_closed(all, workflow, task.id, &task.status)?;
+            }
+        }
+    }
+
+    Ok(())
+}
+
+/// Overwrite the task with `incoming`'s ID if it is one of `updated`,
+/// keeping where it lives and the fields the import does not know about.
+fn update(tasks: &mut [Task], archive: &mut [Task], updated: &[u32], incoming: &Task) -> bool {
+    if !updated.contains(&incoming.id) {
+        return false;
+    }
+
+    let Some(task) = tasks
+        .iter_mut()
+        .chain(archive.iter_mut())
+        .find(|t| t.id == incoming.id)
+    else {
+        return false;
+    };
+
+    *task = Task {
+        owner: task.owner.clone(),
+        parent: incoming.parent.or(task.parent),
+        created_at: task.created_at.or(incoming.created_at),
+        archived_at: task.archived_at,
+        ..incoming.clone()
+    };
+    true
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::test_support::{self, temp_store};
+
+    fn task(id: u32, status: Status, parent: Option<u32>, depends_on: &[u32]) -> Task {
+        Task {
+            parent,
+            depends_on: depends_on.to_vec(),
+            ..test_support::task(id, status)
+        }
+    }
+
+    #[test]
+    fn finds_tasks_by_imported_then_derived_uuid() {
+        let mut imported = task(1, Status::Todo, None, &[]);
+        imported.uuid = Some("a1".into());
+        let created = task(2, Status::Todo, None, &[]);
+        let derived = taskwarrior::uuid("web", &created);
+        let mut clash = task(3, Status::Todo, None, &[]);
+        clash.uuid = Some(derived.clone());
+        let tasks = [imported, created];
+
+        assert_eq!(find_uuid(&tasks, &[], "a1", &["inbox"]), Some(1));
+        assert_eq!(find_uuid(&tasks, &[], &derived, &["inbox", "web"]), Some(2));
+        assert_eq!(find_uuid(&tasks, &[], &derived, &["inbox"]), None);
+        // A task imported with the UUID wins over one it is derived for
+        assert_eq!(find_uuid(&tasks, &[clash], &derived, &["web"]), Some(3));
+    }
+
+    #[test]
+    fn refuses_merged_parent_and_dependency_loops() {
+        let workflow = Workflow::default();
+        let parents = [
+            task(1, Status::Todo, Some(2), &[]),
+            task(2, Status::Todo, Some(1), &[]),
+        ];
+        let deps = [
+            task(1, Status::Todo, None, &[2]),
+            task(2, Status::Todo, None, &[1]),
+        ];
+
+        assert!(check_merged(&parents, &workflow, &[2], &HashMap::new()).is_err());
+        assert!(check_merged(&deps, &workflow, &[2], &HashMap::new()).is_err());
+        assert!(check_merged(&deps, &workflow, &[], &HashMap::new()).is_ok());
+    }
+
+    #[test]
+    fn checks_status_changes_of_updated_tasks() {
+        let workflow = Workflow::default();
+        let todo = workflow.resolve("todo").unwrap();
+        let tasks = [
+            task(1, Status::Done, None, &[]),
+            task(2, Status::Todo, Some(1), &[]),
+        ];
+
+        let err = check_merged(&tasks, &workflow, &[1], &HashMap::from([(1, todo.clone())]));
+        assert!(err.unwrap_err().to_string().contains("open subtasks"));
+
+        // New tasks keep the status they come with
+        assert!(check_merged(&tasks, &workflow, &[1], &HashMap::new()).is_ok());
+        assert!(check_merged(&tasks, &workflow, &[2], &HashMap::from([(2, todo.clone())])).is_ok());
+
+        let mut strict = Workflow::default();
+        strict
+            .transitions
+            .insert("Todo".into(), vec!["InProgress".into()]);
+        let tasks = [task(1, Status::Done, None, &[])];
+
+        let err = check_merged(&tasks, &strict, &[1], &HashMap::from([(1, todo)]));
+        assert!(err.unwrap_err().to_string().contains("does not allow"));
+    }
+
+    fn bundle(tasks: Vec<Task>) -> Bundle {
+        let project = Project {
+            name: "web".into(),
+            created_at: Local::now(),
+            next_task_id: 3,
+        };
+        let mut bundle = Bundle::new(project, tasks);
+        bundle.assign_uuids();
+        bundle
+    }
+
+    #[test]
+    fn merging_a_bundle_again_updates_its_tasks() {
+        let store = temp_store("merge-again");
+        let mut bundle = bundle(vec![
+            task(1, Status::Todo, None, &[]),
+            task(2, Status::Custom("Review".into()), Some(1), &[1]),
+        ]);
+        bundle.workflow = Some(
+            toml::from_str(
+                r#"
+                [[status]]
+                name = "Todo"
+
+                [[status]]
+                name = "Review"
+                kind = "active"
+                "#,
+            )
+            .unwrap(),
+        );
+
+        assert_eq!(merge(&store, bundle.clone(), &[]).unwrap(), (2, 0));
+        assert_eq!(merge(&store, bundle, &[]).unwrap(), (0, 2));
+
+        let tasks = store.load_tasks().unwrap();
+        let ids: Vec<u32> = tasks.iter().map(|t| t.id).collect();
+        assert_eq!(ids, [1, 2]);
+        // Review is not in the built-in workflow; its kind picks the status
+        assert_eq!(tasks[1].status, Status::InProgress);
+        assert_eq!(tasks[1].depends_on, [1]);
+        std::fs::remove_dir_all(store.dir()).ok();
+    }
+
+    #[test]
+    fn refuses_two_imported_tasks_for_one_task() {
+        let existing = task(1, Status::Todo, None, &[]);
+        let mut tasks = vec![existing.clone()];
+        let mut first = task(1, Status::Done, None, &[]);
+        first.uuid = Some(taskwarrior::uuid("test", &existing));
+        let mut second = task(2, Status::Done, None, &[]);
+        second.uuid = Some(taskwarrior::uuid("inbox", &existing));
+
+        let err = merge_into(
+            &mut tasks,
+            &mut Vec::new(),
+            &bundle(vec![first, second]),
+            &Workflow::default(),
+            &["test", "inbox"],
+            |_, count| Ok(10..10 + count),
+        )
+        .unwrap_err();
+
+        assert_eq!(
+            err.to_string(),
+            "more than one imported task matches task 1"
+        );
+    }
+
+    #[test]
+    fn refused_merges_are_found_before_writing() {
+        let store = temp_store("merge-check");
+        let mut existing = task(1, Status::Todo, None, &[]);
+        existing.uuid = Some("a1".into());
+        store.save_tasks(&[existing]).unwrap();
+        let mut strict = Workflow::default();
+        strict
+            .transitions
+            .insert("Todo".into(), vec!["InProgress".into()]);
+        store.save_workflow(&strict).unwrap();
+        let before = std::fs::read_to_string(store.dir().join("tasks.json")).unwrap();
+
+        let mut done = task(1, Status::Done, None, &[]);
+        done.uuid = Some("a1".into());
+        let err = check_merge(&store, &bundle(vec![done]), &[]).unwrap_err();
+
+        assert!(err.to_string().contains("does not allow"), "{}", err);
+        assert_eq!(
+            std::fs::read_to_string(store.dir().join("tasks.json")).unwrap(),
+            before
+        );
+        std::fs::remove_dir_all(store.dir()).ok();
+    }
+}
@@ -0,0 +1,576 @@
+use std::collections::{HashMap, HashSet};
+use std::fmt;
+use std::fs;
+use std::path::Path;
+
+use chrono::{DateTime, Local};
+
+use crate::config;
+use crate::csv;
+use crate::deps;
+use crate::error::{PmError, Result};
+use crate::models::{Config, Task};
+use crate::storage::{ProjectStore, StorageError};
+use crate::tags;
+use crate::tree;
+use crate::workflow::Workflow;
+
+use super::export::Column;
+
+/// How to read the CSV file.
+#[derive(Debug, Clone)]
+pub struct CsvImportOptions {
+    /// Header to read a field from instead of the field's own name, e.g.
+    /// `(Column::Description, "Title")`.
+    pub map: Vec<(Column, String)>,
+    /// Field separator.
+    pub delimiter: char,
+    /// Import nothing if any row has an error.
+    pub strict: bool,
+}
+
+impl Default for CsvImportOptions {
+    fn default() -> Self {
+        CsvImportOptions {
+            map: Vec::new(),
+            delimiter: ',',
+            strict: false,
+        }
+    }
+}
+
+/// A row that could not be imported.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct RowError {
+    pub line: usize,
+    pub message: String,
+}
+
+impl fmt::Display for RowError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "line {}: {}", self.line, self.message)
+    }
+}
+
+/// Outcome of a CSV import.
+#[derive(Debug, Clone)]
+pub struct CsvImport {
+    /// Imported tasks with their new IDs.
+    pub tasks: Vec<Task>,
+    /// Skipped rows.
+    pub errors: Vec<RowError>,
+}
+
+/// A parsed row before it gets an ID in the project.
+struct Row {
+    line: usize,
+    /// ID in the file, which `parent` and `depends_on` refer to.
+    id: Option<u32>,
+    task: Task,
+}
+
+/// Add the rows of a CSV file to a project as new tasks.
+///
+/// Headers are matched to [`Column`] names ignoring case, so files written
+/// by `export` read back as they are; unknown headers are ignored and only
+/// `description` is required. Deadlines use the configured `date_format`,
+/// timestamps RFC 3339. Rows with an `archived_at` go to the archive.
+///
+/// Imported tasks get fresh IDs. `parent` and `depends_on` refer to the
+/// file's `id` column, or to tasks already in the project when no row has
+/// that ID.
+///
+/// Rows with errors are skipped and returned, or with `strict` fail the
+/// whole import. All tasks are added in one undoable update, keeping the
+/// file's timestamps.
+pub fn run(project: &str, path: &Path, options: &CsvImportOptions) -> Result<CsvImport> {
+    let data = fs::read_to_string(path).map_err(|source| StorageError::Io {
+        path: path.to_path_buf(),
+        source,
+    })?;
+    let records = csv::parse(&data, options.delimiter)
+        .map_err(|e| PmError::InvalidInput(format!("{}: {}", path.display(), e)))?;
+
+    let Some((header, records)) = records.split_first() else {
+        return Err(PmError::InvalidInput(format!(
+            "{}: no header line",
+            path.display()
+        )));
+    };
+    let columns = columns(&header.fields, &options.map)?;
+
+    let store = ProjectStore::open(project)?;
+    let workflow = store.load_workflow()?;
+    let cfg = config::load()?;
+    let owner = config::current_user();
+
+    // Every ID in the file, also of rows that fail, so links to them are
+    // not mistaken for links to tasks already in the project
+    let id_index = columns.iter().position(|c| *c == Some(Column::Id));
+    let file_ids: HashSet<u32> = records
+        .iter()
+        .filter_map(|r| id_index.and_then(|i| r.fields.get(i)))
+        .filter_map(|id| id.trim().parse().ok())
+        .collect();
+
+    let mut errors = Vec::new();
+    let mut rows = Vec::new();
+    let mut seen = HashSet::new();
+
+    for record in records {
+        let row = if record.fields.len() > header.fields.len() {
+            Err(format!(
+                "expected {} fields, found {}",
+                header.fields.len(),
+                record.fields.len()
+            ))
+        } else {
+            parse_row(record, &columns, &workflow, &cfg, &owner)
+        };
+
+        match row {
+            Ok(Row { id: Some(id), .. }) if !seen.insert(id) => errors.push(RowError {
+                line: record.line,
+                message: format!("duplicate id {}", id),
+            }),
+            Ok(row) => rows.push(row),
+            Err(message) => errors.push(RowError {
+                line: record.line,
+                message,
+            }),
+        }
+    }
+
+    let tasks = store.import_with_archive(|tasks, archive| {
+        let existing: HashSet<u32> = tasks.iter().chain(archive.iter()).map(|t| t.id).collect();
+        check_links(&mut rows, &mut errors, &file_ids, &existing);
+        errors.sort_by_key(|e| e.line);
+
+        if options.strict && !errors.is_empty() {
+            return Err(PmError::InvalidInput(format!(
+                "{} ({} row(s) with errors, nothing imported)",
+                errors[0],
+                errors.len()
+            )));
+        }
+
+        let new_ids = store.allocate_task_ids(tasks, rows.len() as u32)?;
+        let ids: HashMap<u32, u32> = rows
+            .iter()
+            .zip(new_ids.clone())
+            .filter_map(|(row, new)| row.id.map(|id| (id, new)))
+            .collect();
+        // Links not found in the file point to tasks of the project
+        let link = |id: u32| ids.get(&id).copied().unwrap_or(id);
+
+        let mut imported = Vec::with_capacity(rows.len());
+        for (row, id) in rows.into_iter().zip(new_ids) {
+            let mut task = row.task;
+            task.id = id;
+            task.parent = task.parent.map(link);
+            task.depends_on = task.depends_on.into_iter().map(link).collect();
+
+            if task.archived_at.is_some() {
+                archive.push(task.clone());
+            } else {
+                tasks.push(task.clone());
+            }
+            imported.push(task);
+        }
+
+        Ok(imported)
+    })?;
+
+    Ok(CsvImport { tasks, errors })
+}
+
+/// Column of each header field; `None` for headers that are not imported.
+fn columns(header: &[String], map: &[(Column, String)]) -> Result<Vec<Option<Column>>> {
+    let is = |h: &String, name: &str| h.trim().eq_ignore_ascii_case(name.trim());
+
+    for (column, name) in map {
+        if !header.iter().any(|h| is(h, name)) {
+            return Err(PmError::InvalidInput(format!(
+                "no column '{}' in the header (mapped to {})",
+                name,
+                column.name()
+            )));
+        }
+    }
+
+    let name_of = |column: Column| {
+        map.iter()
+            .rev()
+            .find(|(c, _)| *c == column)
+            .map_or(column.name(), |(_, name)| name.as_str())
+    };
+
+    let columns: Vec<Option<Column>> = header
+        .iter()
+        .map(|h| Column::ALL.into_iter().find(|c| is(h, name_of(*c))))
+        .collect();
+
+    if !columns.contains(&Some(Column::Description)) {
+        return Err(PmError::InvalidInput(
+            "no 'description' column (use --map description=<header>)".into(),
+        ));
+    }
+
+    Ok(columns)
+}
+
+fn parse_row(
+    record: &csv::Record,
+    columns: &[Option<Column>],
+    workflow: &Workflow,
+    cfg: &Config,
+    owner: &str,
+) -> std::result::Result<Row, String> {
+    let mut row = Row {
+        line: record.line,
+        id: None,
+        task: Task {
+            id: 0,
+            description: String::new(),
+            status: workflow.initial().clone(),
+            priority: cfg.default_priority.unwrap_or_default(),
+            deadline: None,
+            owner: owner.to_string(),
+            parent: None,
+            depends_on: Vec::new(),
+            tags: Vec::new(),
+            created_at: None,
+            updated_at: None,
+            completed_at: None,
+            archived_at: None,
+            uuid: None,
+        },
+    };
+
+    for (column, value) in columns.iter().zip(&record.fields) {
+        let value = value.trim();
+        if let (Some(column), false) = (column, value.is_empty()) {
+            set(&mut row, *column, value, workflow, cfg).map_err(|e| e.to_string())?;
+        }
+    }
+
+    if row.task.description.is_empty() {
+        return Err("description is empty".into());
+    }
+
+    Ok(row)
+}
+
+/// Read `value` into the field of `column`, the way `export` writes it.
+fn set(
+    row: &mut Row,
+    column: Column,
+    value: &str,
+    workflow: &Workflow,
+    cfg: &Config,
+) -> Result<()> {
+    let id = |v: &str| {
+        v.trim()
+            .parse::<u32>()
+            .map_err(|_| PmError::InvalidInput(format!("invalid task ID '{}'", v)))
+    };
+    let time = |v: &str| {
+        DateTime::parse_from_rfc3339(v)
+            .map(|t| Some(t.with_timezone(&Local)))
+            .map_err(|_| {
+                PmError::InvalidInput(format!("invalid timestamp '{}' (expected RFC 3339)", v))
+            })
+    };
+    let task = &mut row.task;
+
+    match column {
+        Column::Id => row.id = Some(id(value)?),
+        Column::Description => task.description = value.to_string(),
+        Column::Status => task.status = workflow.resolve(value)?,
+        Column::Priority => task.priority = value.parse().map_err(PmError::InvalidInput)?,
+        Column::Deadline => task.deadline = Some(config::parse_date(cfg, value)?),
+        Column::Owner => task.owner = value.to_string(),
+        Column::Parent => task.parent = Some(id(value)?),
+        Column::DependsOn => {
+            task.depends_on = value.split(',').map(id).collect::<Result<_>>()?;
+        }
+        Column::Tags => {
+            for tag in value.split(',').filter(|t| !t.trim().is_empty()) {
+                let tag = tags::normalize(tag)?;
+                if !task.tags.contains(&tag) {
+                    task.tags.push(tag);
+                }
+            }
+        }
+        Column::CreatedAt => task.created_at = time(value)?,
+        Column::UpdatedAt => task.updated_at = time(value)?,
+        Column::CompletedAt => task.completed_at = time(value)?,
+        Column::ArchivedAt => task.archived_at = time(value)?,
+        Column::Uuid => task.uuid = Some(value.to_string()),
+    }
+
+    Ok(())
+}
+
+/// Skip rows linking to a task that is neither imported nor in the project,
+/// and rows in a parent or dependency loop, until no skipped row is linked
+/// to anymore.
+fn check_links(
+    rows: &mut Vec<Row>,
+    errors: &mut Vec<RowError>,
+    file_ids: &HashSet<u32>,
+    existing: &HashSet<u32>,
+) {
+    loop {
+        let imported: HashSet<u32> = rows.iter().filter_map(|r| r.id).collect();
+        // Rows under their file ID; tasks of the project never link to
+        // them, so loops can only run through these
+        let linked: Vec<Task> = rows
+            .iter()
+            .filter_map(|r| {
+                r.id.map(|id| Task {
+                    id,
+                    ..r.task.clone()
+                })
+            })
+            .collect();
+        let before = rows.len();
+
+        rows.retain(|row| {
+            let missing = row
+                .task
+                .parent
+                .iter()
+                .chain(&row.task.depends_on)
+                .find(|id| {
+                    !imported.contains(id) && (file_ids.contains(id) || !existing.contains(id))
+                });
+
+            let message = match (missing, row.id) {
+                (Some(id), _) if file_ids.contains(id) => {
+                    format!("links to task {}, which was not imported", id)
+                }
+                (Some(id), _) => format!("links to unknown task {}", id),
+                (None, Some(id)) => match loop_link(&linked, id, &row.task) {
+                    Some(message) => message,
+                    None => return true,
+                },
+                (None, None) => return true,
+            };
+
+            errors.push(RowError {
+                line: row.line,
+                message,
+            });
+            false
+        });
+
+        if rows.len() == before {
+            break;
+        }
+    }
+}
+
+/// Why `task`, the row with file ID `id`, closes a parent or dependency
+/// loop among `linked`, if it does.
+fn loop_link(linked: &[Task], id: u32, task: &Task) -> Option<String> {
+    if let Some(parent) = task.parent {
+        if parent == id || tree::descendants(linked, id).iter().any(|t| t.id == parent) {
+            return Some(format!("parent {} is one of its own subtasks", parent));
+        }
+    }
+
+    task.depends_on
+        .iter()
+        .find(|d| deps::would_cycle(linked, id, **d))
+        .map(|d| format!("depends on task {}, which already depends on it", d))
+}
+
+#[cfg(test)]
+mod tests {
+    use chrono::{NaiveDate, TimeZone};
+
+    use super::*;
+    use crate::models::Priority;
+    use crate::test_support::config;
+
+    fn task() -> Task {
+        let at = |h| Some(Local.with_ymd_and_hms(2024, 3, 1, h, 30, 15).unwrap());
+
+        Task {
+            id: 7,
+            description: "Write, \"quote\" and\nbreak".into(),
+            status: Workflow::default().resolve("done").unwrap(),
+            priority: Priority::Level(8),
+            deadline: NaiveDate::from_ymd_opt(2024, 4, 2),
+            owner: "alice".into(),
+            parent: Some(2),
+            depends_on: vec![3, 5],
+            tags: vec!["api".into(), "bug".into()],
+            created_at: at(9),
+            updated_at: at(11),
+            completed_at: at(10),
+            archived_at: at(12),
+            uuid: Some("5f88bd74-d837-8ec3-8fe7-1bd78d9006e5".into()),
+        }
+    }
+
+    fn rows(data: &str, map: &[(Column, String)]) -> Vec<std::result::Result<Row, String>> {
+        let records = csv::parse(data, ',').unwrap();
+        let columns = columns(&records[0].fields, map).unwrap();
+
+        records[1..]
+            .iter()
+            .map(|r| parse_row(r, &columns, &Workflow::default(), &config(), "me"))
+            .collect()
+    }
+
+    #[test]
+    fn exported_rows_read_back_unchanged() {
+        let task = task();
+        let mut data = String::new();
+        let header: Vec<&str> = Column::ALL.iter().map(|c| c.name()).collect();
+        csv::write_record(&mut data, &header, ',');
+        let fields: Vec<String> = Column::ALL
+            .iter()
+            .map(|c| c.value(&task, &config()))
+            .collect();
+        csv::write_record(&mut data, &fields, ',');
+
+        let row = rows(&data, &[]).remove(0).unwrap();
+
+        assert_eq!(row.id, Some(7));
+        assert_eq!(row.task, Task { id: 0, ..task });
+    }
+
+    #[test]
+    fn maps_headers_and_fills_defaults() {
+        let data = "Title,OWNER,extra\nShip it,bob,x\n,bob,x\n";
+        let map = [(Column::Description, "title".to_string())];
+
+        let mut rows = rows(data, &map);
+
+        assert_eq!(rows.pop().unwrap().err().unwrap(), "description is empty");
+        let task = rows.pop().unwrap().unwrap().task;
+        assert_eq!(task.description, "Ship it");
+        assert_eq!(task.owner, "bob");
+        assert_eq!(task.status, *Workflow::default().initial());
+        assert_eq!(task.created_at, None);
+    }
+
+    #[test]
+    fn reports_bad_fields_and_headers() {
+        let data = "description,priority,created_at,depends_on\n\
+                    a,sky-high,,\n\
+                    b,,yesterday,\n\
+                    c,,,1;2\n";
+
+        let errors: Vec<String> = rows(data, &[])
+            .into_iter()
+            .map(|r| r.err().unwrap())
+            .collect();
+
+        assert_eq!(errors.len(), 3);
+        assert!(errors[1].contains("expected RFC 3339"), "{}", errors[1]);
+        assert!(errors[2].contains("invalid task ID '1;2'"), "{}", errors[2]);
+        assert!(columns(&["name".to_string()], &[]).is_err());
+        assert!(columns(
+            &["description".to_string()],
+            &[(Column::Owner, "who".to_string())]
+        )
+        .is_err());
+    }
+
+    #[test]
+    fn skips_rows_linking_to_skipped_or_unknown_tasks() {
+        let row = |line, id, parent| Row {
+            line,
+            id: Some(id),
+            task: Task {
+                parent: Some(parent),
+                depends_on: Vec::new(),
+                ..task()
+            },
+        };
+        // 2 is in the file but failed, 3 links to it, 4 to 3; 5 links to an
+        // existing task and 6 to nothing
+        let mut rows = vec![row(3, 3, 2), row(4, 4, 3), row(5, 5, 40), row(6, 6, 41)];
+        let mut errors = Vec::new();
+
+        check_links(
+            &mut rows,
+            &mut errors,
+            &HashSet::from([2, 3, 4, 5, 6]),
+            &HashSet::from([40]),
+        );
+
+        assert_eq!(rows.iter().map(|r| r.line).collect::<Vec<_>>(), [5]);
+        let lines: Vec<usize> = errors.iter().map(|e| e.line).collect();
+        assert_eq!(lines, [3, 6, 4]);
+        assert_eq!(errors[1].message, "links to unknown task 41");
+    }
+
+    #[test]
+    fn skips_rows_in_dependency_loops() {
+        let row = |line, id, depends_on: &[u32]| Row {
+            line,
+            id: Some(id),
+            task: Task {
+                parent: None,
+                depends_on: depends_on.to_vec(),
+                ..task()
+            },
+        };
+        // 1 and 2 wait on each other, 3 waits on 1; 4 waits on itself
+        let mut rows = vec![
+            row(2, 1, &[2]),
+            row(3, 2, &[1]),
+            row(4, 3, &[1]),
+            row(5, 4, &[4]),
+        ];
+        let mut errors = Vec::new();
+
+        check_links(
+            &mut rows,
+            &mut errors,
+            &HashSet::from([1, 2, 3, 4]),
+            &HashSet::new(),
+        );
+
+        assert!(rows.is_empty());
+        let lines: Vec<usize> = errors.iter().map(|e| e.line).collect();
+        assert_eq!(lines, [2, 3, 5, 4]);
+        assert_eq!(
+            errors[0].message,
+            "depends on task 2, which already depends on it"
+        );
+        assert_eq!(errors[3].message, "links to task 1, which was not imported");
+    }
+
+    #[test]
+    fn skips_rows_in_parent_loops() {
+        let row = |line, id, parent| Row {
+            line,
+            id: Some(id),
+            task: Task {
+                parent,
+                depends_on: Vec::new(),
+                ..task()
+            },
+        };
+        // 1 and 2 are each other's parent, 3 is under an existing task
+        let mut rows = vec![row(2, 1, Some(2)), row(3, 2, Some(1)), row(4, 3, Some(40))];
+        let mut errors = Vec::new();
+
+        check_links(
+            &mut rows,
+            &mut errors,
+            &HashSet::from([1, 2, 3]),
+            &HashSet::from([40]),
+        );
+
+        assert_eq!(rows.iter().map(|r| r.line).collect::<Vec<_>>(), [4]);
+        assert_eq!(errors.len(), 2);
+        assert_eq!(errors[0].message, "parent 2 is one of its own subtasks");
+    }
+}
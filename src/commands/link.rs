@@ -0,0 +1,70 @@
+use crate::deps;
+use crate::error::{PmError, Result};
+use crate::models::Task;
+use crate::storage::ProjectStore;
+
+/// Make task `id` depend on task `dependency` and return the updated task.
+///
+/// Links that would make tasks wait on each other are refused.
+pub fn run(project: &str, id: u32, dependency: u32) -> Result<Task> {
+    let store = ProjectStore::open(project)?;
+
+    store.update_with_archive(|tasks, archive| {
+        let all: Vec<Task> = tasks.iter().chain(archive.iter()).cloned().collect();
+        check(&all, id, dependency)?;
+
+        let task = find(tasks, id)?;
+        if !task.depends_on.contains(&dependency) {
+            task.depends_on.push(dependency);
+        }
+        Ok(task.clone())
+    })
+}
+
+/// Remove the dependency of task `id` on task `dependency`.
+pub fn run_unlink(project: &str, id: u32, dependency: u32) -> Result<Task> {
+    let store = ProjectStore::open(project)?;
+
+    store.update_tasks(|tasks| {
+        let task = find(tasks, id)?;
+
+        let before = task.depends_on.len();
+        task.depends_on.retain(|d| *d != dependency);
+
+        if task.depends_on.len() == before {
+            return Err(PmError::InvalidInput(format!(
+                "task {} does not depend on task {}",
+                id, dependency
+            )));
+        }
+        Ok(task.clone())
+    })
+}
+
+/// Refuse making `id` depend on itself, on a task not in `all` (active and
+/// archived tasks), or on a task that already waits on `id`.
+pub(crate) fn check(all: &[Task], id: u32, dependency: u32) -> Result<()> {
+    if id == dependency {
+        return Err(PmError::InvalidInput(format!(
+            "task {} cannot depend on itself",
+            id
+        )));
+    }
+    if !all.iter().any(|t| t.id == dependency) {
+        return Err(PmError::TaskNotFound(dependency));
+    }
+    if deps::would_cycle(all, id, dependency) {
+        return Err(PmError::InvalidInput(format!(
+            "task {} already depends on task {}, linking would create a cycle",
+            dependency, id
+        )));
+    }
+    Ok(())
+}
+
+fn find(tasks: &mut [Task], id: u32) -> Result<&mut Task> {
+    tasks
+        .iter_mut()
+        .find(|t| t.id == id)
+        .ok_or(PmError::TaskNotFound(id))
+}
@@ -1,9 +1,25 @@
 pub mod add_task;
+pub mod archive_task;
+pub mod bulk;
 pub mod create;
+pub mod delete_task;
 pub mod done_task;
+pub mod edit_task;
 pub mod export;
 pub mod git;
+pub mod graph;
+pub mod import;
+pub mod import_csv;
+pub mod link;
 pub mod list;
 pub mod note;
+pub mod repair;
+pub mod restore_task;
+pub mod set_status;
+pub mod tag;
+pub mod tags;
+pub mod task_log;
 pub mod tasks;
 pub mod tui;
+pub mod undo;
+pub mod workflow;
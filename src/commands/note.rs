@@ -1,16 +1,7 @@
-use dirs::home_dir;
-use std::fs::OpenOptions;
-use std::io::Write;
+use crate::error::Result;
+use crate::storage::ProjectStore;
 
-pub fn run(project: &str, note: &str) {
-    let path = home_dir()
-        .unwrap()
-        .join(".pmcli")
-        .join(project)
-        .join("notes.md");
-
-    let mut file = OpenOptions::new().append(true).open(path).unwrap();
-
-    writeln!(file, "- {}", note).unwrap();
-    println!("📝 Note added");
+/// Append a bullet to the project's `notes.md`.
+pub fn run(project: &str, note: &str) -> Result<()> {
+    Ok(ProjectStore::open(project)?.append_note(note)?)
 }
@@ -0,0 +1,29 @@
+use crate::error::Result;
+use crate::storage::ProjectStore;
+
+/// Outcome of repairing one project.
+#[derive(Debug, Clone)]
+pub struct RepairReport {
+    pub project: String,
+    /// `(old, new)` ID pairs, in file order.
+    pub renumbered: Vec<(u32, u32)>,
+}
+
+/// Renumber duplicate task IDs in one project, or in every project.
+pub fn run(project: Option<&str>) -> Result<Vec<RepairReport>> {
+    let names = match project {
+        Some(p) => vec![p.to_string()],
+        None => ProjectStore::list()?.into_iter().map(|p| p.name).collect(),
+    };
+
+    names
+        .into_iter()
+        .map(|name| {
+            let renumbered = ProjectStore::open(&name)?.repair_task_ids()?;
+            Ok(RepairReport {
+                project: name,
+                renumbered,
+            })
+        })
+        .collect()
+}
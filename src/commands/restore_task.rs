@@ -0,0 +1,23 @@
+use crate::error::{PmError, Result};
+use crate::models::Task;
+use crate::storage::ProjectStore;
+
+/// Move an archived task back to the active task list and return it.
+pub fn run(project: &str, id: u32) -> Result<Task> {
+    let store = ProjectStore::open(project)?;
+
+    store.update_with_archive(|tasks, archive| {
+        let pos = archive
+            .iter()
+            .position(|t| t.id == id)
+            .ok_or(PmError::TaskNotFound(id))?;
+
+        let mut task = archive.remove(pos);
+        task.archived_at = None;
+
+        // Keep the list in ID order
+        let at = tasks.iter().position(|t| t.id > id).unwrap_or(tasks.len());
+        tasks.insert(at, task.clone());
+        Ok(task)
+    })
+}
@@ -0,0 +1,61 @@
+use crate::error::{PmError, Result};
+use crate::models::{Status, Task};
+use crate::storage::ProjectStore;
+use crate::tree;
+use crate::workflow::Workflow;
+
+/// Move a task to `status` (a name from the project's workflow) and return
+/// the updated task. Transitions the workflow forbids are rejected, and so
+/// is completing a task with open subtasks unless `force` is set.
+pub fn run(project: &str, id: u32, status: &str, force: bool) -> Result<Task> {
+    set(&ProjectStore::open(project)?, id, status, force)
+}
+
+/// [`run`] on an open project.
+pub(crate) fn set(store: &ProjectStore, id: u32, status: &str, force: bool) -> Result<Task> {
+    update(store, id, force, |workflow, _| workflow.resolve(status))
+}
+
+/// Put a done or cancelled task back to the workflow's first status.
+pub fn run_reopen(project: &str, id: u32) -> Result<Task> {
+    update(
+        &ProjectStore::open(project)?,
+        id,
+        false,
+        |workflow, task| {
+            if !workflow.is_closed(&task.status) {
+                return Err(PmError::InvalidInput(format!(
+                    "task {} is {}, only done or cancelled tasks can be reopened",
+                    task.id, task.status
+                )));
+            }
+            Ok(workflow.initial().clone())
+        },
+    )
+}
+
+fn update(
+    store: &ProjectStore,
+    id: u32,
+    force: bool,
+    next: impl FnOnce(&Workflow, &Task) -> Result<Status>,
+) -> Result<Task> {
+    let workflow = store.load_workflow()?;
+
+    store.update_tasks(|tasks| {
+        let pos = tasks
+            .iter()
+            .position(|t| t.id == id)
+            .ok_or(PmError::TaskNotFound(id))?;
+
+        let status = next(&workflow, &tasks[pos])?;
+        workflow.check_transition(&tasks[pos].status, &status)?;
+        if !force {
+            tree::check_subtasks_closed(tasks, &workflow, id, &status)?;
+        }
+
+        let task = &mut tasks[pos];
+        task.status = status;
+        Ok(task.clone())
+    })
+}
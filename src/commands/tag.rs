@@ -0,0 +1,56 @@
+use crate::error::{PmError, Result};
+use crate::models::Task;
+use crate::storage::ProjectStore;
+use crate::tags;
+
+/// Add tags to task `id` and return the updated task. Tags it already has
+/// are left alone.
+pub fn run(project: &str, id: u32, new_tags: &[String]) -> Result<Task> {
+    let new_tags = parse(new_tags)?;
+    let store = ProjectStore::open(project)?;
+
+    store.update_tasks(|tasks| {
+        let task = find(tasks, id)?;
+
+        for tag in new_tags {
+            if !task.tags.contains(&tag) {
+                task.tags.push(tag);
+            }
+        }
+        Ok(task.clone())
+    })
+}
+
+/// Remove tags from task `id` and return the updated task.
+pub fn run_untag(project: &str, id: u32, old_tags: &[String]) -> Result<Task> {
+    let old_tags = parse(old_tags)?;
+    let store = ProjectStore::open(project)?;
+
+    store.update_tasks(|tasks| {
+        let task = find(tasks, id)?;
+
+        if let Some(missing) = old_tags.iter().find(|tag| !task.tags.contains(tag)) {
+            return Err(PmError::InvalidInput(format!(
+                "task {} is not tagged '{}'",
+                id, missing
+            )));
+        }
+
+        task.tags.retain(|tag| !old_tags.contains(tag));
+        Ok(task.clone())
+    })
+}
+
+fn parse(tags: &[String]) -> Result<Vec<String>> {
+    if tags.is_empty() {
+        return Err(PmError::InvalidInput("no tags given".into()));
+    }
+    tags.iter().map(|t| tags::normalize(t)).collect()
+}
+
+fn find(tasks: &mut [Task], id: u32) -> Result<&mut Task> {
+    tasks
+        .iter_mut()
+        .find(|t| t.id == id)
+        .ok_or(PmError::TaskNotFound(id))
+}
@@ -0,0 +1,19 @@
+use std::collections::BTreeMap;
+
+use crate::error::Result;
+use crate::storage::ProjectStore;
+
+/// Every tag used by the project's active tasks with the number of tasks
+/// carrying it, in alphabetical order.
+pub fn run(project: &str) -> Result<BTreeMap<String, usize>> {
+    let store = ProjectStore::open(project)?;
+    let mut counts = BTreeMap::new();
+
+    for task in store.load_tasks()? {
+        for tag in task.tags {
+            *counts.entry(tag).or_insert(0) += 1;
+        }
+    }
+
+    Ok(counts)
+}
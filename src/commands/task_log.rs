@@ -0,0 +1,31 @@
+use crate::error::{PmError, Result};
+use crate::history::HistoryEntry;
+use crate::storage::ProjectStore;
+
+/// Recorded changes to task `id`, oldest first.
+///
+/// Works for archived and deleted tasks too, as long as something was
+/// recorded for them.
+pub fn run(project: &str, id: u32) -> Result<Vec<HistoryEntry>> {
+    let store = ProjectStore::open(project)?;
+
+    let entries: Vec<HistoryEntry> = store
+        .load_history()?
+        .into_iter()
+        .filter(|e| e.task == id)
+        .collect();
+
+    if entries.is_empty() {
+        let exists = store
+            .load_tasks()?
+            .iter()
+            .chain(store.load_archive()?.iter())
+            .any(|t| t.id == id);
+
+        if !exists {
+            return Err(PmError::TaskNotFound(id));
+        }
+    }
+
+    Ok(entries)
+}
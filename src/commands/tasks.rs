@@ -1,41 +1,412 @@
-use dirs::home_dir;
-use std::env;
-use std::fs;
+use std::borrow::Borrow;
+use std::cmp::Reverse;
+use std::str::FromStr;
 
-use crate::models::{Status, Task};
+use chrono::Local;
 
-pub fn run(project: &str, filter: Option<&str>) {
-    let path = home_dir()
-        .unwrap()
-        .join(".pmcli")
-        .join(project)
-        .join("tasks.json");
-
-    let tasks: Vec<Task> = serde_json::from_str(&fs::read_to_string(&path).unwrap()).unwrap();
-
-    let current_user = env::var("USER").unwrap_or("unknown".into());
-
-    for task in tasks {
-        // ===== FILTER =====
-        if let Some(f) = filter {
-            match f {
-                "mine" if task.owner != current_user => continue,
-                "todo" if !matches!(task.status, Status::Todo) => continue,
-                "done" if !matches!(task.status, Status::Done) => continue,
-                "blocked" if !matches!(task.status, Status::Blocked) => continue,
-                _ => {}
+use crate::config;
+use crate::deps;
+use crate::error::{PmError, Result};
+use crate::models::{Config, Priority, Status, StatusKind, Task};
+use crate::query::{self, Query};
+use crate::storage::ProjectStore;
+use crate::tags::TagFilter;
+use crate::tree::{self, Progress};
+use crate::workflow::Workflow;
+
+/// Which tasks to list and in what order. The default lists every active
+/// task by ID; all given criteria must match.
+#[derive(Debug, Clone, Default)]
+pub struct TaskQuery {
+    /// Filter expression, see [`crate::query`].
+    pub query: Option<String>,
+    /// `mine`, `open` (not done or cancelled), `ready` (open and not waiting
+    /// on anything) or a status name from the project's workflow.
+    pub filter: Option<String>,
+    /// Status names from the workflow; a task matches any of them.
+    pub statuses: Vec<String>,
+    /// Owner name, `me` for the current user.
+    pub owner: Option<String>,
+    pub priority: Option<String>,
+    /// Deadline on or before this date (in the configured `date_format`).
+    pub due_before: Option<String>,
+    /// Deadline on or after this date.
+    pub due_after: Option<String>,
+    /// Open tasks whose deadline has passed.
+    pub overdue: bool,
+    /// Case-insensitive text in the description.
+    pub text: Option<String>,
+    /// `backend` requires a tag, `!frontend` excludes it.
+    pub tags: Vec<String>,
+    pub sort: SortKey,
+    pub reverse: bool,
+    pub limit: Option<usize>,
+    /// List archived tasks after the active ones.
+    pub include_archived: bool,
+}
+
+/// Tasks of a project matching `query`, in the requested order.
+pub fn run(project: &str, query: &TaskQuery) -> Result<Vec<Task>> {
+    let store = ProjectStore::open(project)?;
+    let workflow = store.load_workflow()?;
+    let (shown, all) = load(&store, query.include_archived)?;
+
+    select(shown, &all, query, &workflow, &config::load()?)
+}
+
+/// Order of listed tasks.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum SortKey {
+    /// Order of the task list, which is by ID.
+    #[default]
+    Id,
+    /// Highest priority first.
+    Priority,
+    /// Earliest deadline first, tasks without one last.
+    Deadline,
+    /// Board order of the project's workflow.
+    Status,
+    /// Oldest first; tasks from before creation times were recorded lead.
+    Created,
+}
+
+impl SortKey {
+    /// Stable, so tasks that compare equal keep their list order.
+    pub fn sort<T: Borrow<Task>>(self, tasks: &mut [T], workflow: &Workflow) {
+        match self {
+            SortKey::Id => tasks.sort_by_key(|t| t.borrow().id),
+            SortKey::Priority => tasks.sort_by_key(|t| Reverse(t.borrow().priority)),
+            SortKey::Deadline => {
+                tasks.sort_by_key(|t| (t.borrow().deadline.is_none(), t.borrow().deadline))
+            }
+            SortKey::Status => tasks.sort_by_key(|t| workflow.position(&t.borrow().status)),
+            SortKey::Created => tasks.sort_by_key(|t| t.borrow().created_at),
+        }
+    }
+}
+
+impl FromStr for SortKey {
+    type Err = String;
+
+    fn from_str(s: &str) -> std::result::Result<Self, Self::Err> {
+        match s {
+            "id" => Ok(SortKey::Id),
+            "priority" => Ok(SortKey::Priority),
+            "deadline" | "due" => Ok(SortKey::Deadline),
+            "status" => Ok(SortKey::Status),
+            "created" => Ok(SortKey::Created),
+            _ => Err(format!(
+                "unknown sort key '{}' (expected id, priority, deadline, status or created)",
+                s
+            )),
+        }
+    }
+}
+
+/// A task in tree order, see [`run_tree`].
+#[derive(Debug, Clone)]
+pub struct TreeRow {
+    pub task: Task,
+    /// 0 for top-level tasks.
+    pub depth: usize,
+    /// Completion of its subtasks, `None` without subtasks.
+    pub progress: Option<Progress>,
+    /// Dependencies that are not done yet. A task waiting on something is
+    /// shown as blocked whatever its status.
+    pub waiting_on: Vec<u32>,
+    /// What the task's status means in the project's workflow.
+    pub kind: StatusKind,
+}
+
+/// Like [`run`], but with every task followed by its subtasks. The sort
+/// order applies to top-level tasks and to the subtasks under each parent.
+///
+/// Progress counts all subtasks, including ones the query hides.
+pub fn run_tree(project: &str, query: &TaskQuery) -> Result<Vec<TreeRow>> {
+    let store = ProjectStore::open(project)?;
+    let workflow = store.load_workflow()?;
+    let (shown, all) = load(&store, query.include_archived)?;
+
+    let selected = select(shown.clone(), &all, query, &workflow, &config::load()?)?;
+
+    Ok(tree::flatten(&selected)
+        .into_iter()
+        .map(|(depth, task)| TreeRow {
+            task: task.clone(),
+            depth,
+            progress: tree::progress(&shown, &workflow, task.id),
+            waiting_on: deps::waiting_on(&all, &workflow, task),
+            kind: workflow.kind(&task.status),
+        })
+        .collect())
+}
+
+/// Tasks to show, and every task of the project for looking up
+/// dependencies.
+fn load(store: &ProjectStore, include_archived: bool) -> Result<(Vec<Task>, Vec<Task>)> {
+    let tasks = store.load_tasks()?;
+    let archive = store.load_archive()?;
+
+    let mut all = tasks.clone();
+    all.extend(archive);
+
+    Ok(if include_archived {
+        (all.clone(), all)
+    } else {
+        (tasks, all)
+    })
+}
+
+/// Apply every criterion of `query`, then sort and limit.
+fn select(
+    tasks: Vec<Task>,
+    all: &[Task],
+    query: &TaskQuery,
+    workflow: &Workflow,
+    cfg: &Config,
+) -> Result<Vec<Task>> {
+    let date = |d: &Option<String>| d.as_deref().map(|d| config::parse_date(cfg, d)).transpose();
+
+    let expr = query
+        .query
+        .as_deref()
+        .map(|q| Query::parse(q, workflow, cfg))
+        .transpose()?;
+    let filter = query
+        .filter
+        .as_deref()
+        .map(|f| Filter::parse(f, workflow))
+        .transpose()?;
+    let statuses = query
+        .statuses
+        .iter()
+        .map(|s| workflow.resolve(s))
+        .collect::<Result<Vec<Status>>>()?;
+    let priority = query
+        .priority
+        .as_deref()
+        .map(|p| p.parse::<Priority>().map_err(PmError::InvalidInput))
+        .transpose()?;
+    let due_before = date(&query.due_before)?;
+    let due_after = date(&query.due_after)?;
+    let tags = TagFilter::parse(&query.tags)?;
+    let text = query.text.as_ref().map(|t| t.to_lowercase());
+
+    let current_user = config::current_user();
+    let owner = query.owner.as_deref().map(|o| match o {
+        "me" => current_user.as_str(),
+        other => other,
+    });
+
+    let today = Local::now().date_naive();
+    let ctx = query::Context {
+        workflow,
+        tasks: all,
+        user: &current_user,
+        today,
+    };
+
+    let mut tasks: Vec<Task> = tasks
+        .into_iter()
+        .filter(|task| match &filter {
+            Some(Filter::Mine) => task.owner.eq_ignore_ascii_case(&current_user),
+            Some(Filter::Open) => !workflow.is_closed(&task.status),
+            Some(Filter::Ready) => {
+                !workflow.is_closed(&task.status) && !deps::is_blocked(all, workflow, task)
             }
+            Some(Filter::Blocked) => deps::is_blocked(all, workflow, task),
+            Some(Filter::Status(status)) => deps::has_status(all, workflow, task, status),
+            None => true,
+        })
+        .filter(|task| {
+            statuses.is_empty()
+                || statuses
+                    .iter()
+                    .any(|s| deps::has_status(all, workflow, task, s))
+        })
+        .filter(|task| owner.is_none_or(|o| task.owner.eq_ignore_ascii_case(o)))
+        .filter(|task| priority.is_none_or(|p| task.priority == p))
+        .filter(|task| due_before.is_none_or(|d| task.deadline.is_some_and(|dl| dl <= d)))
+        .filter(|task| due_after.is_none_or(|d| task.deadline.is_some_and(|dl| dl >= d)))
+        .filter(|task| {
+            !query.overdue
+                || (!workflow.is_closed(&task.status) && task.deadline.is_some_and(|dl| dl < today))
+        })
+        .filter(|task| {
+            text.as_ref()
+                .is_none_or(|t| task.description.to_lowercase().contains(t))
+        })
+        .filter(|task| tags.matches(task))
+        .filter(|task| expr.as_ref().is_none_or(|q| q.matches(task, &ctx)))
+        .collect();
+
+    query.sort.sort(&mut tasks, workflow);
+    if query.reverse {
+        tasks.reverse();
+    }
+    if let Some(limit) = query.limit {
+        tasks.truncate(limit);
+    }
+
+    Ok(tasks)
+}
+
+enum Filter {
+    Mine,
+    Open,
+    Ready,
+    /// Any blocked status, or waiting on a dependency.
+    Blocked,
+    Status(Status),
+}
+
+impl Filter {
+    fn parse(name: &str, workflow: &Workflow) -> Result<Self> {
+        match name {
+            "mine" => Ok(Filter::Mine),
+            "open" => Ok(Filter::Open),
+            "ready" => Ok(Filter::Ready),
+            _ => match workflow.resolve(name) {
+                Ok(status) => Ok(Filter::Status(status)),
+                // Also without a `blocked` status in the workflow, as tasks
+                // waiting on a dependency are still shown as BLOCKED
+                Err(_) if name == "blocked" => Ok(Filter::Blocked),
+                Err(_) => Err(PmError::InvalidInput(format!(
+                    "unknown filter '{}' (expected mine, open, ready, blocked or a status name)",
+                    name
+                ))),
+            },
         }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::test_support::{config, task};
 
-        let status_label = match task.status {
-            Status::Todo => "TODO",
-            Status::Done => "DONE",
-            Status::Blocked => "BLOCKED",
+    fn ids(tasks: &[Task], query: &TaskQuery) -> Vec<u32> {
+        select(
+            tasks.to_vec(),
+            tasks,
+            query,
+            &Workflow::default(),
+            &config(),
+        )
+        .unwrap()
+        .iter()
+        .map(|t| t.id)
+        .collect()
+    }
+
+    #[test]
+    fn all_criteria_must_match() {
+        let me = config::current_user();
+        let tasks = [
+            Task {
+                owner: me.to_uppercase(),
+                tags: vec!["api".into()],
+                priority: Priority::High,
+                ..task(1, Status::Todo)
+            },
+            Task {
+                owner: me.clone(),
+                tags: vec!["api".into()],
+                priority: Priority::High,
+                ..task(2, Status::Done)
+            },
+            Task {
+                owner: me.clone(),
+                priority: Priority::High,
+                ..task(3, Status::InProgress)
+            },
+            Task {
+                owner: "someone-else".into(),
+                tags: vec!["api".into()],
+                priority: Priority::High,
+                ..task(4, Status::Todo)
+            },
+            Task {
+                owner: me.clone(),
+                tags: vec!["api".into()],
+                ..task(5, Status::Todo)
+            },
+        ];
+
+        let query = |filter: &str| TaskQuery {
+            filter: Some(filter.into()),
+            tags: vec!["api".into()],
+            priority: Some("high".into()),
+            ..Default::default()
+        };
+        assert_eq!(ids(&tasks, &query("open")), [1, 4]);
+        assert_eq!(ids(&tasks, &query("mine")), [1, 2]);
+
+        // `mine`, `--owner me` and `owner:me` ignore case alike
+        let mine = TaskQuery {
+            filter: Some("mine".into()),
+            ..Default::default()
+        };
+        let owner_me = TaskQuery {
+            owner: Some("me".into()),
+            ..Default::default()
+        };
+        let query_me = TaskQuery {
+            query: Some("owner:me".into()),
+            ..Default::default()
+        };
+        assert_eq!(ids(&tasks, &mine), [1, 2, 3, 5]);
+        assert_eq!(ids(&tasks, &owner_me), ids(&tasks, &mine));
+        assert_eq!(ids(&tasks, &query_me), ids(&tasks, &mine));
+    }
+
+    #[test]
+    fn ties_keep_id_order_before_reverse_and_limit() {
+        let deadline = |d: &str| d.parse().ok();
+        let tasks = [
+            Task {
+                priority: Priority::Low,
+                deadline: deadline("2024-03-02"),
+                ..task(1, Status::Todo)
+            },
+            Task {
+                priority: Priority::High,
+                ..task(2, Status::Done)
+            },
+            Task {
+                priority: Priority::Low,
+                deadline: deadline("2024-03-01"),
+                ..task(3, Status::Todo)
+            },
+            Task {
+                priority: Priority::Level(7),
+                deadline: deadline("2024-03-01"),
+                ..task(4, Status::Todo)
+            },
+        ];
+        let sorted = |sort, reverse, limit| TaskQuery {
+            sort,
+            reverse,
+            limit,
+            ..Default::default()
         };
 
-        println!(
-            "[{}] {:<8} | {:<6} | {} | owner: {}",
-            task.id, status_label, task.priority, task.description, task.owner
+        assert_eq!(
+            ids(&tasks, &sorted(SortKey::Priority, false, None)),
+            [2, 4, 1, 3]
+        );
+        assert_eq!(
+            ids(&tasks, &sorted(SortKey::Priority, true, None)),
+            [3, 1, 4, 2]
+        );
+        assert_eq!(
+            ids(&tasks, &sorted(SortKey::Deadline, false, None)),
+            [3, 4, 1, 2]
+        );
+        assert_eq!(
+            ids(&tasks, &sorted(SortKey::Status, false, Some(2))),
+            [1, 3]
         );
+        assert_eq!(ids(&tasks, &sorted(SortKey::Id, true, Some(1))), [4]);
     }
 }
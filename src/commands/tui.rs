@@ -1,7 +1,9 @@
-use std::{fs, io, time::Duration};
+use std::{collections::HashSet, io, str::FromStr, time::Duration};
+
+use chrono::{DateTime, Local};
 
 use crossterm::{
-    event::{self, Event, KeyCode},
+    event::{self, Event, KeyCode, KeyModifiers},
     execute,
     terminal::{disable_raw_mode, enable_raw_mode, EnterAlternateScreen, LeaveAlternateScreen},
 };
@@ -10,191 +12,698 @@ use ratatui::{
     backend::CrosstermBackend,
     layout::{Constraint, Direction, Layout},
     style::{Color, Style},
+    text::{Line, Span},
     widgets::{Block, Borders, List, ListItem, Paragraph, Wrap},
-    Terminal,
+    Frame, Terminal,
 };
 
-use crate::models::{Status, Task};
-use dirs::home_dir;
+use crate::config;
+use crate::deps;
+use crate::error::{PmError, Result};
+use crate::history::HistoryEntry;
+use crate::models::{Config, Priority, Status, StatusKind, Task};
+use crate::query::{self, Query};
+use crate::storage::{FileStamp, ProjectStore};
+use crate::tree;
+
+use super::tasks::SortKey;
+use crate::workflow::Workflow;
+
+type Term = Terminal<CrosstermBackend<io::Stdout>>;
 
-pub fn run(project: &str) {
-    let path = home_dir()
-        .unwrap()
-        .join(".pmcli")
-        .join(project)
-        .join("tasks.json");
+/// History entries shown in the detail panel.
+const HISTORY_LINES: usize = 8;
 
-    let mut tasks: Vec<Task> = serde_json::from_str(&fs::read_to_string(&path).unwrap()).unwrap();
+/// Interactive task browser. Archived tasks are listed after the active
+/// ones when `include_archived` is set.
+pub fn run(project: &str, include_archived: bool) -> Result<()> {
+    let store = ProjectStore::open(project)?;
+    let mut app = App::new(&store, include_archived)?;
 
-    enable_raw_mode().unwrap();
+    enable_raw_mode()?;
     let mut stdout = io::stdout();
-    execute!(stdout, EnterAlternateScreen).unwrap();
+    execute!(stdout, EnterAlternateScreen)?;
 
     let backend = CrosstermBackend::new(stdout);
-    let mut terminal = Terminal::new(backend).unwrap();
+    let mut terminal = Terminal::new(backend)?;
 
-    let mut selected: usize = 0;
-    let mut search = String::new();
-    let mut search_mode = false;
+    // Restore the terminal even when the loop fails
+    let result = event_loop(&mut terminal, &mut app);
 
-    loop {
-        let filtered: Vec<&Task> = if search.is_empty() {
-            tasks.iter().collect()
-        } else {
-            tasks
-                .iter()
-                .filter(|t| t.description.to_lowercase().contains(&search))
-                .collect()
+    disable_raw_mode()?;
+    execute!(terminal.backend_mut(), LeaveAlternateScreen)?;
+    terminal.show_cursor()?;
+
+    result
+}
+
+struct App<'a> {
+    store: &'a ProjectStore,
+    workflow: Workflow,
+    tasks: Vec<Task>,
+    archive: Vec<Task>,
+    /// Active and archived tasks, for looking up dependencies.
+    all: Vec<Task>,
+    /// List archived tasks after the active ones.
+    show_archived: bool,
+    history: Vec<HistoryEntry>,
+    /// For tag colours, saved queries and dates in the search.
+    cfg: Config,
+    stamp: [FileStamp; 3],
+
+    /// Tasks whose subtasks are hidden.
+    collapsed: HashSet<u32>,
+    /// Tasks marked with space; actions apply to all of them.
+    marked: HashSet<u32>,
+    sort: SortKey,
+    selected: usize,
+    search: String,
+    /// `search` parsed as a filter expression; `Err` while it does not parse
+    /// (e.g. half typed), in which case it is matched as plain text.
+    query: std::result::Result<Query, String>,
+    search_mode: bool,
+    message: Option<String>,
+}
+
+impl<'a> App<'a> {
+    fn new(store: &'a ProjectStore, include_archived: bool) -> Result<Self> {
+        let cfg = config::load()?;
+        let mut app = App {
+            store,
+            workflow: Workflow::default(),
+            tasks: Vec::new(),
+            archive: Vec::new(),
+            all: Vec::new(),
+            show_archived: include_archived,
+            history: Vec::new(),
+            cfg,
+            stamp: store.tasks_stamp(),
+            collapsed: HashSet::new(),
+            marked: HashSet::new(),
+            sort: SortKey::Id,
+            selected: 0,
+            search: String::new(),
+            query: Ok(Query::default()),
+            search_mode: false,
+            message: None,
         };
 
-        if selected >= filtered.len() && !filtered.is_empty() {
-            selected = filtered.len() - 1;
+        app.reload()?;
+        Ok(app)
+    }
+
+    fn reload(&mut self) -> Result<()> {
+        self.stamp = self.store.tasks_stamp();
+        self.workflow = self.store.load_workflow()?;
+        self.tasks = self.store.load_tasks()?;
+        self.archive = self.store.load_archive()?;
+        self.all = self.tasks.iter().chain(&self.archive).cloned().collect();
+        self.history = self.store.load_history()?;
+
+        Ok(())
+    }
+
+    /// Pick up changes other pmcli processes made to the project.
+    fn reload_if_changed(&mut self) {
+        if self.store.tasks_stamp() == self.stamp {
+            return;
         }
 
-        terminal
-            .draw(|f| {
-                let layout = Layout::default()
-                    .direction(Direction::Vertical)
-                    .constraints([Constraint::Length(3), Constraint::Min(1)])
-                    .split(f.size());
-
-                let body = Layout::default()
-                    .direction(Direction::Horizontal)
-                    .constraints([Constraint::Percentage(45), Constraint::Percentage(55)])
-                    .split(layout[1]);
-
-                // ===== SEARCH BAR =====
-                let search_bar = Paragraph::new(if search_mode {
-                    format!("🔍 /{}", search)
-                } else {
-                    "Press / to search | ↑↓ Enter b q".into()
-                })
-                .block(Block::default().borders(Borders::ALL).title("Search"));
-
-                f.render_widget(search_bar, layout[0]);
-
-                // ===== TASK LIST =====
-                let items: Vec<ListItem> = filtered
-                    .iter()
-                    .map(|t| {
-                        let color = match t.status {
-                            Status::Todo => Color::White,
-                            Status::Done => Color::Green,
-                            Status::Blocked => Color::Red,
-                        };
-
-                        ListItem::new(format!(
-                            "[{}] {:<8} {}",
-                            t.id,
-                            format!("{:?}", t.status),
-                            t.description
-                        ))
-                        .style(Style::default().fg(color))
-                    })
-                    .collect();
-
-                let list = List::new(items)
-                    .block(Block::default().title("Tasks").borders(Borders::ALL))
-                    .highlight_style(Style::default().bg(Color::Blue));
-
-                let mut state = ratatui::widgets::ListState::default();
-                state.select(Some(selected));
-                f.render_stateful_widget(list, body[0], &mut state);
-
-                // ===== DETAIL PANEL =====
-                if let Some(task) = filtered.get(selected) {
-                    let detail = Paragraph::new(format!(
-                        "ID       : {}\n\
-                     Status   : {:?}\n\
-                     Priority : {}\n\
-                     Deadline : {}\n\
-                     Owner    : {}\n\n\
-                     Description:\n{}",
-                        task.id,
-                        task.status,
-                        task.priority,
-                        task.deadline.map(|d| d.to_string()).unwrap_or("—".into()),
-                        task.owner,
-                        task.description
-                    ))
-                    .wrap(Wrap { trim: false })
-                    .block(Block::default().title("Detail").borders(Borders::ALL));
-
-                    f.render_widget(detail, body[1]);
-                }
+        self.message = match self.reload() {
+            Ok(()) => Some("↻ reloaded, project changed on disk".into()),
+            Err(e) => Some(format!("❌ {}", e)),
+        };
+    }
+
+    fn set_search(&mut self, search: String) {
+        self.query = Query::parse(&search, &self.workflow, &self.cfg).map_err(|e| e.to_string());
+        self.search = search;
+        self.selected = 0;
+    }
+
+    /// Tasks shown in the list with their tree depth, in display order.
+    /// While searching, matches are shown regardless of collapsed parents.
+    fn visible(&self) -> Vec<(usize, &Task)> {
+        let user = config::current_user();
+        let ctx = query::Context {
+            workflow: &self.workflow,
+            tasks: &self.all,
+            user: &user,
+            today: Local::now().date_naive(),
+        };
+        let search = self.search.to_lowercase();
+        let archived: &[Task] = if self.show_archived {
+            &self.archive
+        } else {
+            &[]
+        };
+
+        let mut matches: Vec<&Task> = self
+            .tasks
+            .iter()
+            .chain(archived)
+            .filter(|t| match &self.query {
+                Ok(query) => query.matches(t, &ctx),
+                Err(_) => t.description.to_lowercase().contains(&search),
             })
-            .unwrap();
+            .collect();
 
-        if event::poll(Duration::from_millis(200)).unwrap() {
-            if let Event::Key(key) = event::read().unwrap() {
-                match key.code {
-                    KeyCode::Char('q') if !search_mode => break,
+        self.sort.sort(&mut matches, &self.workflow);
 
-                    KeyCode::Char('/') if !search_mode => {
-                        search_mode = true;
-                        search.clear();
-                    }
+        let mut rows = tree::flatten(matches);
 
-                    KeyCode::Esc if search_mode => {
-                        search_mode = false;
-                        search.clear();
-                        selected = 0;
-                    }
+        if self.search.is_empty() {
+            let mut hidden_below = None;
+            rows.retain(|(depth, task)| {
+                if hidden_below.is_some_and(|d| *depth > d) {
+                    return false;
+                }
+                hidden_below = self.collapsed.contains(&task.id).then_some(*depth);
+                true
+            });
+        }
 
-                    KeyCode::Backspace if search_mode => {
-                        search.pop();
-                        selected = 0;
-                    }
+        rows
+    }
 
-                    KeyCode::Char(c) if search_mode => {
-                        search.push(c.to_ascii_lowercase());
-                        selected = 0;
-                    }
+    fn waiting_on(&self, task: &Task) -> Vec<u32> {
+        deps::waiting_on(&self.all, &self.workflow, task)
+    }
 
-                    KeyCode::Down if !search_mode => {
-                        if selected + 1 < filtered.len() {
-                            selected += 1;
-                        }
-                    }
+    fn has_subtasks(&self, id: u32) -> bool {
+        self.tasks.iter().any(|t| t.parent == Some(id))
+    }
+
+    /// IDs an action applies to: the marked tasks, or else the selected one.
+    fn targets(&self, current: &Option<Task>) -> Vec<u32> {
+        if self.marked.is_empty() {
+            return current.iter().map(|t| t.id).collect();
+        }
+
+        let mut ids: Vec<u32> = self.marked.iter().copied().collect();
+        ids.sort();
+        ids
+    }
+
+    /// Apply a status change to tasks `ids` on the latest on-disk data, so
+    /// edits made by other pmcli processes while the TUI is open are not
+    /// overwritten. Archived tasks are left alone. All tasks change in a
+    /// single update, or none does.
+    fn set_status(
+        &mut self,
+        ids: &[u32],
+        next: impl Fn(&Workflow, &Status) -> Result<Status>,
+    ) -> Result<()> {
+        let workflow = &self.workflow;
+
+        self.store.update_tasks(|latest| {
+            let mut changed = Vec::new();
+
+            for &id in ids {
+                let task = latest.iter_mut().find(|t| t.id == id).ok_or_else(|| {
+                    PmError::InvalidInput(format!("task {} is archived, restore it first", id))
+                })?;
+
+                let status = next(workflow, &task.status)?;
+                workflow.check_transition(&task.status, &status)?;
+                task.status = status.clone();
+                changed.push((id, status));
+            }
+
+            // Checked after all changes, so a parent can be completed
+            // together with its subtasks
+            for (id, status) in &changed {
+                tree::check_subtasks_closed(latest, workflow, *id, status)?;
+            }
+
+            Ok(())
+        })?;
+
+        // Read back what was saved, with the timestamps the update set
+        self.reload()
+    }
+
+    /// Undo (or redo) the project's last change; returns what happened.
+    fn replay(&mut self, undo: bool) -> Result<String> {
+        let entry = if undo {
+            self.store.undo()?
+        } else {
+            self.store.redo()?
+        };
+
+        self.reload()?;
+
+        Ok(match (entry, undo) {
+            (Some(e), true) => format!("↩ undid {}", e.operation.summary()),
+            (Some(e), false) => format!("↪ redid {}", e.operation.summary()),
+            (None, true) => "nothing to undo".into(),
+            (None, false) => "nothing to redo".into(),
+        })
+    }
+
+    /// Archive the task, or restore it when it is archived.
+    fn toggle_archived(&mut self, task: &Task) -> Result<()> {
+        if task.archived_at.is_some() {
+            super::restore_task::run(self.store.name(), task.id)?;
+        } else {
+            super::archive_task::run(self.store.name(), &[task.id])?;
+        }
+
+        self.reload()
+    }
+
+    /// Archive the marked tasks that are not archived yet.
+    fn archive_marked(&mut self) -> Result<()> {
+        let ids: Vec<u32> = self
+            .tasks
+            .iter()
+            .filter(|t| self.marked.contains(&t.id))
+            .map(|t| t.id)
+            .collect();
+
+        super::archive_task::run(self.store.name(), &ids)?;
+        self.reload()
+    }
+
+    /// Colour from the config's `tag_colors`, otherwise one picked from the
+    /// tag name so it stays the same between runs.
+    fn tag_color(&self, tag: &str) -> Color {
+        if let Some(color) = self
+            .cfg
+            .tag_colors
+            .get(tag)
+            .and_then(|c| Color::from_str(c).ok())
+        {
+            return color;
+        }
+
+        const PALETTE: [Color; 6] = [
+            Color::Cyan,
+            Color::Magenta,
+            Color::LightGreen,
+            Color::LightYellow,
+            Color::LightBlue,
+            Color::LightRed,
+        ];
+        let hash = tag
+            .bytes()
+            .fold(0usize, |h, b| h.wrapping_mul(31) + b as usize);
+        PALETTE[hash % PALETTE.len()]
+    }
+
+    fn priority_color(priority: Priority) -> Color {
+        match priority.level() {
+            9.. => Color::LightRed,
+            7..=8 => Color::LightYellow,
+            5..=6 => Color::Gray,
+            _ => Color::DarkGray,
+        }
+    }
+
+    fn status_color(&self, task: &Task) -> Color {
+        if task.archived_at.is_some() {
+            return Color::DarkGray;
+        }
+        if !self.waiting_on(task).is_empty() {
+            return Color::Red;
+        }
+
+        if let Some(color) = self
+            .workflow
+            .color(&task.status)
+            .and_then(|c| Color::from_str(c).ok())
+        {
+            return color;
+        }
+
+        match self.workflow.kind(&task.status) {
+            StatusKind::Todo => Color::White,
+            StatusKind::Active => Color::Yellow,
+            StatusKind::Done => Color::Green,
+            StatusKind::Blocked => Color::Red,
+            StatusKind::Cancelled => Color::DarkGray,
+        }
+    }
+}
+
+fn event_loop(terminal: &mut Term, app: &mut App) -> Result<()> {
+    loop {
+        // ===== RELOAD ON EXTERNAL CHANGE =====
+        app.reload_if_changed();
+
+        let len = app.visible().len();
+        if app.selected >= len && len > 0 {
+            app.selected = len - 1;
+        }
+
+        terminal.draw(|f| draw(f, app))?;
+
+        if !event::poll(Duration::from_millis(200))? {
+            continue;
+        }
+
+        let Event::Key(key) = event::read()? else {
+            continue;
+        };
+
+        let current = app.visible().get(app.selected).map(|(_, t)| (*t).clone());
+
+        let result = match key.code {
+            KeyCode::Char('q') if !app.search_mode => break,
+
+            KeyCode::Char('/') if !app.search_mode => {
+                app.search_mode = true;
+                app.set_search(String::new());
+                Ok(())
+            }
+
+            KeyCode::Esc if app.search_mode => {
+                app.search_mode = false;
+                app.set_search(String::new());
+                Ok(())
+            }
+
+            KeyCode::Backspace if app.search_mode => {
+                let mut search = app.search.clone();
+                search.pop();
+                app.set_search(search);
+                Ok(())
+            }
 
-                    KeyCode::Up if !search_mode => {
-                        selected = selected.saturating_sub(1);
+            KeyCode::Char(c) if app.search_mode => {
+                let search = format!("{}{}", app.search, c);
+                app.set_search(search);
+                Ok(())
+            }
+
+            KeyCode::Down if !app.search_mode && app.selected + 1 < len => {
+                app.selected += 1;
+                Ok(())
+            }
+
+            KeyCode::Up if !app.search_mode => {
+                app.selected = app.selected.saturating_sub(1);
+                Ok(())
+            }
+
+            // Step forward through the project's workflow
+            KeyCode::Enter if !app.search_mode => {
+                let ids = app.targets(&current);
+                app.set_status(&ids, |workflow, status| {
+                    workflow.next_in_cycle(status).cloned().ok_or_else(|| {
+                        PmError::InvalidInput(format!("no status to move '{}' to", status))
+                    })
+                })
+            }
+
+            // Toggle the workflow's blocked status
+            KeyCode::Char('b') if !app.search_mode => {
+                let ids = app.targets(&current);
+                app.set_status(&ids, |workflow, status| {
+                    if workflow.kind(status) == StatusKind::Blocked {
+                        return Ok(workflow.initial().clone());
                     }
 
-                    KeyCode::Enter if !filtered.is_empty() && !search_mode => {
-                        let id = filtered[selected].id;
-                        if let Some(t) = tasks.iter_mut().find(|t| t.id == id) {
-                            t.status = match t.status {
-                                Status::Todo => Status::Done,
-                                Status::Done => Status::Todo,
-                                Status::Blocked => Status::Blocked,
-                            };
-                            save(&path, &tasks);
-                        }
+                    workflow
+                        .first_of_kind(StatusKind::Blocked)
+                        .cloned()
+                        .ok_or_else(|| {
+                            PmError::InvalidInput("workflow has no blocked status".into())
+                        })
+                })
+            }
+
+            // Mark the selected task for bulk actions and move on
+            KeyCode::Char(' ') if !app.search_mode => {
+                if let Some(task) = &current {
+                    if !app.marked.remove(&task.id) {
+                        app.marked.insert(task.id);
                     }
+                    if app.selected + 1 < len {
+                        app.selected += 1;
+                    }
+                }
+                Ok(())
+            }
+
+            KeyCode::Esc if !app.marked.is_empty() => {
+                app.marked.clear();
+                Ok(())
+            }
 
-                    KeyCode::Char('b') if !filtered.is_empty() && !search_mode => {
-                        let id = filtered[selected].id;
-                        if let Some(t) = tasks.iter_mut().find(|t| t.id == id) {
-                            t.status = match t.status {
-                                Status::Blocked => Status::Todo,
-                                _ => Status::Blocked,
-                            };
-                            save(&path, &tasks);
-                        }
+            // Toggle ordering by priority
+            KeyCode::Char('p') if !app.search_mode => {
+                app.sort = match app.sort {
+                    SortKey::Priority => SortKey::Id,
+                    _ => SortKey::Priority,
+                };
+                app.selected = 0;
+                Ok(())
+            }
+
+            // Fold and unfold subtasks
+            KeyCode::Left if !app.search_mode => {
+                if let Some(task) = &current {
+                    if app.has_subtasks(task.id) {
+                        app.collapsed.insert(task.id);
                     }
+                }
+                Ok(())
+            }
 
-                    _ => {}
+            KeyCode::Right if !app.search_mode => {
+                if let Some(task) = &current {
+                    app.collapsed.remove(&task.id);
                 }
+                Ok(())
             }
-        }
+
+            // Archive the selected task, or restore it when archived;
+            // archive all marked tasks
+            KeyCode::Char('a') if !app.search_mode && !app.marked.is_empty() => {
+                app.archive_marked().map(|()| app.marked.clear())
+            }
+
+            KeyCode::Char('a') if !app.search_mode => match &current {
+                Some(task) => app.toggle_archived(task),
+                None => Ok(()),
+            },
+
+            KeyCode::Char('u') if !app.search_mode => {
+                app.message = Some(app.replay(true).unwrap_or_else(|e| format!("❌ {}", e)));
+                continue;
+            }
+
+            KeyCode::Char('r')
+                if !app.search_mode && key.modifiers.contains(KeyModifiers::CONTROL) =>
+            {
+                app.message = Some(app.replay(false).unwrap_or_else(|e| format!("❌ {}", e)));
+                continue;
+            }
+
+            _ => continue,
+        };
+
+        app.message = result.err().map(|e| format!("❌ {}", e));
     }
 
-    disable_raw_mode().unwrap();
-    execute!(terminal.backend_mut(), LeaveAlternateScreen).unwrap();
-    terminal.show_cursor().unwrap();
+    Ok(())
+}
+
+/// `3, 4 (waiting on 4)`, or `—` without dependencies.
+fn depends_on(task: &Task, waiting: &[u32]) -> String {
+    if task.depends_on.is_empty() {
+        return "—".into();
+    }
+
+    let list = |ids: &[u32]| {
+        ids.iter()
+            .map(|id| id.to_string())
+            .collect::<Vec<_>>()
+            .join(", ")
+    };
+
+    if waiting.is_empty() {
+        list(&task.depends_on)
+    } else {
+        format!("{} (waiting on {})", list(&task.depends_on), list(waiting))
+    }
 }
 
-fn save(path: &std::path::Path, tasks: &Vec<Task>) {
-    fs::write(path, serde_json::to_string_pretty(tasks).unwrap()).unwrap();
+fn draw(f: &mut Frame, app: &App) {
+    let visible = app.visible();
+
+    let layout = Layout::default()
+        .direction(Direction::Vertical)
+        .constraints([Constraint::Length(3), Constraint::Min(1)])
+        .split(f.size());
+
+    let body = Layout::default()
+        .direction(Direction::Horizontal)
+        .constraints([Constraint::Percentage(45), Constraint::Percentage(55)])
+        .split(layout[1]);
+
+    // ===== SEARCH BAR =====
+    let search_bar = Paragraph::new(if app.search_mode {
+        match &app.query {
+            Ok(_) => format!("🔍 /{}", app.search),
+            Err(e) => format!("🔍 /{}   (text search — {})", app.search, e),
+        }
+    } else {
+        "Press / to search | ↑↓ ←→ space Enter b a p u ^r q".into()
+    })
+    .block(
+        Block::default()
+            .borders(Borders::ALL)
+            .title(match &app.message {
+                Some(m) => format!("Search — {}", m),
+                None => "Search".into(),
+            }),
+    );
+
+    f.render_widget(search_bar, layout[0]);
+
+    // ===== TASK LIST =====
+    let items: Vec<ListItem> = visible
+        .iter()
+        .map(|(depth, t)| {
+            let fold = match (app.has_subtasks(t.id), app.collapsed.contains(&t.id)) {
+                (false, _) => "  ",
+                (true, false) => "▾ ",
+                (true, true) => "▸ ",
+            };
+
+            let mut line = vec![
+                Span::raw(format!(
+                    "{}[{}] {:<11} ",
+                    if app.marked.contains(&t.id) {
+                        "● "
+                    } else {
+                        ""
+                    },
+                    t.id,
+                    if app.waiting_on(t).is_empty() {
+                        t.status.label()
+                    } else {
+                        "BLOCKED".into()
+                    },
+                )),
+                Span::styled(
+                    format!("{:<6} ", t.priority),
+                    Style::default().fg(App::priority_color(t.priority)),
+                ),
+            ];
+
+            line.push(Span::raw(format!(
+                "{}{}{}{}",
+                "  ".repeat(*depth),
+                fold,
+                t.description,
+                tree::progress(&app.tasks, &app.workflow, t.id)
+                    .map(|p| format!(" ({}%)", p.percent()))
+                    .unwrap_or_default(),
+            )));
+
+            for tag in &t.tags {
+                line.push(Span::raw(" "));
+                line.push(Span::styled(
+                    format!(" {} ", tag),
+                    Style::default().fg(Color::Black).bg(app.tag_color(tag)),
+                ));
+            }
+
+            if t.archived_at.is_some() {
+                line.push(Span::raw(" (archived)"));
+            }
+
+            ListItem::new(Line::from(line)).style(Style::default().fg(app.status_color(t)))
+        })
+        .collect();
+
+    let list = List::new(items)
+        .block(
+            Block::default()
+                .title(match app.marked.len() {
+                    0 => "Tasks".into(),
+                    n => format!("Tasks — {} marked (Esc to clear)", n),
+                })
+                .borders(Borders::ALL),
+        )
+        .highlight_style(Style::default().bg(Color::Blue));
+
+    let mut state = ratatui::widgets::ListState::default();
+    state.select(Some(app.selected));
+    f.render_stateful_widget(list, body[0], &mut state);
+
+    // ===== DETAIL PANEL =====
+    if let Some((_, task)) = visible.get(app.selected) {
+        let timestamp = |at: Option<DateTime<Local>>| {
+            at.map(|at| at.format("%Y-%m-%d %H:%M").to_string())
+                .unwrap_or("—".into())
+        };
+
+        let mut text = format!(
+            "ID        : {}\n\
+             Status    : {}{}\n\
+             Priority  : {}\n\
+             Deadline  : {}\n\
+             Owner     : {}\n\
+             Tags      : {}\n\
+             Parent    : {}\n\
+             Subtasks  : {}\n\
+             Depends on: {}\n\
+             Created   : {}\n\
+             Updated   : {}\n\
+             Completed : {}\n\n\
+             Description:\n{}",
+            task.id,
+            task.status.label(),
+            task.archived_at
+                .map(|at| format!(" (archived {})", at.format("%Y-%m-%d")))
+                .unwrap_or_default(),
+            task.priority,
+            task.deadline.map(|d| d.to_string()).unwrap_or("—".into()),
+            task.owner,
+            if task.tags.is_empty() {
+                "—".into()
+            } else {
+                task.tags.join(", ")
+            },
+            task.parent.map(|p| p.to_string()).unwrap_or("—".into()),
+            tree::progress(&app.tasks, &app.workflow, task.id)
+                .map(|p| format!("{}/{} done ({}%)", p.done, p.total, p.percent()))
+                .unwrap_or("—".into()),
+            depends_on(task, &app.waiting_on(task)),
+            timestamp(task.created_at),
+            timestamp(task.updated_at),
+            timestamp(task.completed_at),
+            task.description
+        );
+
+        // Most recent changes last, like `pmcli task-log`
+        let history: Vec<&HistoryEntry> =
+            app.history.iter().filter(|e| e.task == task.id).collect();
+
+        if !history.is_empty() {
+            text.push_str("\n\nHistory:");
+            for e in &history[history.len().saturating_sub(HISTORY_LINES)..] {
+                text.push_str(&format!(
+                    "\n{} {} {}{}",
+                    e.at.format("%m-%d %H:%M"),
+                    e.user,
+                    e.change,
+                    match (&e.from, &e.to) {
+                        (Some(from), Some(to)) => format!(" {} → {}", from, to),
+                        _ => String::new(),
+                    }
+                ));
+            }
+        }
+
+        let detail = Paragraph::new(text)
+            .wrap(Wrap { trim: false })
+            .block(Block::default().title("Detail").borders(Borders::ALL));
+
+        f.render_widget(detail, body[1]);
+    }
 }
@@ -0,0 +1,14 @@
+use crate::error::Result;
+use crate::journal::JournalEntry;
+use crate::storage::ProjectStore;
+
+/// Revert the project's most recent change. Returns `None` when there is
+/// nothing left to undo.
+pub fn run(project: &str) -> Result<Option<JournalEntry>> {
+    ProjectStore::open(project)?.undo()
+}
+
+/// Make the most recently undone change again.
+pub fn run_redo(project: &str) -> Result<Option<JournalEntry>> {
+    ProjectStore::open(project)?.redo()
+}
@@ -0,0 +1,27 @@
+use std::path::PathBuf;
+
+use crate::error::{PmError, Result};
+use crate::storage::ProjectStore;
+use crate::workflow::Workflow;
+
+/// The project's workflow (the built-in one unless customised).
+pub fn run(project: &str) -> Result<Workflow> {
+    Ok(ProjectStore::open(project)?.load_workflow()?)
+}
+
+/// Write the built-in workflow to `workflow.toml` as a starting point for
+/// customisation and return the file's path.
+pub fn run_init(project: &str) -> Result<PathBuf> {
+    let store = ProjectStore::open(project)?;
+    let path = store.dir().join("workflow.toml");
+
+    if path.exists() {
+        return Err(PmError::InvalidInput(format!(
+            "{} already exists",
+            path.display()
+        )));
+    }
+
+    store.save_workflow(&Workflow::default())?;
+    Ok(path)
+}
@@ -1,28 +1,69 @@
+use chrono::NaiveDate;
+use std::env;
 use std::fs;
+use std::io;
 use std::path::PathBuf;
 
-use crate::models::Config;
+use crate::error::{PmError, Result};
+use crate::models::{Config, Priority};
 
-pub fn load() -> Config {
-    let path = config_path();
+/// Settings from `config.toml`, or the defaults when there is none.
+///
+/// A file that does not parse, or names an unknown `default_priority`, is
+/// reported rather than silently ignored.
+pub fn load() -> Result<Config> {
+    let Some(path) = config_path() else {
+        return Ok(default());
+    };
 
-    if let Ok(data) = fs::read_to_string(&path) {
-        toml::from_str(&data).unwrap_or(default())
-    } else {
-        default()
-    }
+    let data = match fs::read_to_string(&path) {
+        Ok(data) => data,
+        Err(e) if e.kind() == io::ErrorKind::NotFound => return Ok(default()),
+        Err(e) => return Err(e.into()),
+    };
+
+    toml::from_str(&data)
+        .map_err(|e| PmError::InvalidInput(format!("{}: {}", path.display(), e.message())))
+}
+
+/// Parse a user-supplied date with the configured `date_format`.
+pub fn parse_date(cfg: &Config, input: &str) -> Result<NaiveDate> {
+    let date_format = cfg.date_format.as_deref().unwrap_or("%Y-%m-%d");
+
+    NaiveDate::parse_from_str(input, date_format).map_err(|_| {
+        PmError::InvalidInput(format!(
+            "invalid date '{}' (expected format {})",
+            input, date_format
+        ))
+    })
+}
+
+/// Format a date with the configured `date_format`, so it reads back with
+/// [`parse_date`].
+pub fn format_date(cfg: &Config, date: NaiveDate) -> String {
+    date.format(cfg.date_format.as_deref().unwrap_or("%Y-%m-%d"))
+        .to_string()
+}
+
+/// Login name of whoever runs pmcli: `$USER`, or the system's name for the
+/// process owner when it is unset. New tasks are owned by it, `owner:me`
+/// matches it and the history records it.
+pub fn current_user() -> String {
+    env::var("USER").unwrap_or_else(|_| whoami::username())
 }
 
 fn default() -> Config {
     Config {
-        default_priority: Some("medium".into()),
+        default_priority: Some(Priority::Medium),
         date_format: Some("%Y-%m-%d".into()),
+        tag_colors: Default::default(),
+        queries: Default::default(),
     }
 }
 
-fn config_path() -> PathBuf {
-    let mut path = dirs::config_dir().unwrap();
+fn config_path() -> Option<PathBuf> {
+    let mut path = dirs::config_dir()?;
     path.push("pmcli");
     path.push("config.toml");
-    path
+    Some(path)
 }
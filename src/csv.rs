@@ -0,0 +1,184 @@
+//! Comma-separated values as described in RFC 4180.
+
+/// Append one record to `out`, quoting fields that contain the delimiter, a
+/// quote or a line break. Records end with CRLF.
+pub fn write_record<S: AsRef<str>>(out: &mut String, fields: &[S], delimiter: char) {
+    for (i, field) in fields.iter().enumerate() {
+        if i > 0 {
+            out.push(delimiter);
+        }
+
+        let field = field.as_ref();
+        if field.contains([delimiter, '"', '\r', '\n']) {
+            out.push('"');
+            out.push_str(&field.replace('"', "\"\""));
+            out.push('"');
+        } else {
+            out.push_str(field);
+        }
+    }
+
+    out.push_str("\r\n");
+}
+
+/// A record read by [`parse`].
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Record {
+    /// Line the record starts on, counting from 1.
+    pub line: usize,
+    pub fields: Vec<String>,
+}
+
+/// Split `input` into records.
+///
+/// Quoted fields may contain the delimiter, line breaks and `""` for a
+/// quote; a quote inside an unquoted field is kept as is. Records end with
+/// CRLF or LF, empty lines are skipped and a leading byte order mark, as
+/// written by some spreadsheets, is ignored.
+pub fn parse(input: &str, delimiter: char) -> Result<Vec<Record>, String> {
+    let input = input.strip_prefix('\u{feff}').unwrap_or(input);
+    let mut chars = input.chars().peekable();
+    let mut records = Vec::new();
+    let mut line = 1;
+
+    while chars.peek().is_some() {
+        let start = line;
+        let mut fields = Vec::new();
+        let mut field = String::new();
+        let mut quoted = false;
+
+        loop {
+            match chars.next() {
+                Some('"') if field.is_empty() && !quoted => {
+                    quoted = true;
+                    loop {
+                        match chars.next() {
+                            Some('"') if chars.peek() == Some(&'"') => {
+                                chars.next();
+                                field.push('"');
+                            }
+                            Some('"') => break,
+                            Some(c) => {
+                                if c == '\n' {
+                                    line += 1;
+                                }
+                                field.push(c);
+                            }
+                            None => return Err(format!("line {}: missing closing '\"'", start)),
+                        }
+                    }
+
+                    match chars.peek() {
+                        None | Some('\r' | '\n') => {}
+                        Some(&c) if c == delimiter => {}
+                        Some(_) => {
+                            return Err(format!(
+                                "line {}: unexpected text after closing '\"'",
+                                line
+                            ))
+                        }
+                    }
+                }
+                Some(c) if c == delimiter => {
+                    fields.push(std::mem::take(&mut field));
+                    quoted = false;
+                }
+                Some('\r') if chars.peek() == Some(&'\n') => {}
+                Some('\n') | None => {
+                    line += 1;
+                    fields.push(field);
+                    break;
+                }
+                Some(c) => field.push(c),
+            }
+        }
+
+        if fields.len() > 1 || !fields[0].is_empty() || quoted {
+            records.push(Record {
+                line: start,
+                fields,
+            });
+        }
+    }
+
+    Ok(records)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn fields(records: &[Record]) -> Vec<Vec<&str>> {
+        records
+            .iter()
+            .map(|r| r.fields.iter().map(String::as_str).collect())
+            .collect()
+    }
+
+    #[test]
+    fn quotes_only_fields_that_need_it() {
+        let mut out = String::new();
+        write_record(
+            &mut out,
+            &["plain", "a,b", "say \"hi\"", "two\nlines", ""],
+            ',',
+        );
+        write_record(&mut out, &["a,b", "c;d"], ';');
+
+        assert_eq!(
+            out,
+            "plain,\"a,b\",\"say \"\"hi\"\"\",\"two\nlines\",\r\na,b;\"c;d\"\r\n"
+        );
+    }
+
+    #[test]
+    fn written_records_read_back() {
+        let rows = [
+            vec!["id", "description", "tags"],
+            vec!["1", "Fix \"login\", then\r\ndeploy", "api,bug"],
+            vec!["2", "", "ünïcode ✓"],
+        ];
+        for delimiter in [',', ';', '\t'] {
+            let mut out = String::new();
+            for row in &rows {
+                write_record(&mut out, row, delimiter);
+            }
+
+            let records = parse(&out, delimiter).unwrap();
+
+            assert_eq!(fields(&records), rows);
+            let lines: Vec<usize> = records.iter().map(|r| r.line).collect();
+            assert_eq!(lines, [1, 2, 4]);
+        }
+    }
+
+    #[test]
+    fn reads_what_spreadsheets_write() {
+        let input = "\u{feff}a,b\n\n1,say \"x\"\n\"\",\n2";
+
+        let records = parse(input, ',').unwrap();
+
+        assert_eq!(
+            fields(&records),
+            [
+                vec!["a", "b"],
+                vec!["1", "say \"x\""],
+                vec!["", ""],
+                vec!["2"]
+            ]
+        );
+        assert_eq!(records[1].line, 3);
+    }
+
+    #[test]
+    fn reports_broken_quotes() {
+        assert_eq!(
+            parse("a\n\"open,\nstill open", ',').unwrap_err(),
+            "line 2: missing closing '\"'"
+        );
+        assert_eq!(
+            parse("\"done\"x,y", ',').unwrap_err(),
+            "line 1: unexpected text after closing '\"'"
+        );
+    }
+}
@@ -0,0 +1,110 @@
+use std::collections::HashSet;
+
+use crate::models::{Status, StatusKind, Task};
+use crate::workflow::Workflow;
+
+/// Dependencies of `task` that are not done yet; always empty for done or
+/// cancelled tasks. IDs of tasks that no longer exist are ignored.
+///
+/// `tasks` should include archived tasks, which dependencies may have moved
+/// to.
+pub fn waiting_on(tasks: &[Task], workflow: &Workflow, task: &Task) -> Vec<u32> {
+    if workflow.is_closed(&task.status) {
+        return Vec::new();
+    }
+
+    task.depends_on
+        .iter()
+        .copied()
+        .filter(|id| {
+            tasks
+                .iter()
+                .find(|t| t.id == *id)
+                .is_some_and(|dep| workflow.kind(&dep.status) != StatusKind::Done)
+        })
+        .collect()
+}
+
+/// Whether `task` is blocked: its status is a blocked one of the workflow,
+/// or it waits on a dependency and is shown as BLOCKED.
+pub fn is_blocked(tasks: &[Task], workflow: &Workflow, task: &Task) -> bool {
+    workflow.kind(&task.status) == StatusKind::Blocked
+        || !waiting_on(tasks, workflow, task).is_empty()
+}
+
+/// Whether `task` has `status` as shown to the user: tasks waiting on a
+/// dependency count as having every blocked status.
+pub fn has_status(tasks: &[Task], workflow: &Workflow, task: &Task, status: &Status) -> bool {
+    task.status == *status
+        || (workflow.kind(status) == StatusKind::Blocked
+            && !waiting_on(tasks, workflow, task).is_empty())
+}
+
+/// Whether making `id` depend on `dependency` would close a loop, i.e.
+/// `dependency` already depends on `id`, directly or indirectly.
+pub fn would_cycle(tasks: &[Task], id: u32, dependency: u32) -> bool {
+    let mut seen = HashSet::new();
+    let mut pending = vec![dependency];
+
+    while let Some(current) = pending.pop() {
+        if current == id {
+            return true;
+        }
+        if !seen.insert(current) {
+            continue;
+        }
+        if let Some(task) = tasks.iter().find(|t| t.id == current) {
+            pending.extend(&task.depends_on);
+        }
+    }
+
+    false
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::models::Status;
+    use crate::test_support;
+
+    fn task(id: u32, status: Status, depends_on: &[u32]) -> Task {
+        Task {
+            depends_on: depends_on.to_vec(),
+            ..test_support::task(id, status)
+        }
+    }
+
+    #[test]
+    fn waiting_tasks_count_as_blocked() {
+        let workflow = Workflow::default();
+        let tasks = [
+            task(1, Status::Todo, &[]),
+            task(2, Status::Todo, &[1]),
+            task(3, Status::Blocked, &[]),
+            task(4, Status::Done, &[1]),
+        ];
+        let blocked = workflow.resolve("blocked").unwrap();
+        let todo = workflow.resolve("todo").unwrap();
+
+        let ids = |f: &dyn Fn(&Task) -> bool| -> Vec<u32> {
+            tasks.iter().filter(|t| f(t)).map(|t| t.id).collect()
+        };
+
+        assert_eq!(ids(&|t| is_blocked(&tasks, &workflow, t)), [2, 3]);
+        assert_eq!(ids(&|t| has_status(&tasks, &workflow, t, &blocked)), [2, 3]);
+        assert_eq!(ids(&|t| has_status(&tasks, &workflow, t, &todo)), [1, 2]);
+    }
+
+    #[test]
+    fn finds_indirect_cycles() {
+        let tasks = [
+            task(1, Status::Todo, &[2]),
+            task(2, Status::Todo, &[3]),
+            task(3, Status::Todo, &[]),
+        ];
+
+        assert!(would_cycle(&tasks, 3, 1));
+        assert!(would_cycle(&tasks, 1, 1));
+        assert!(!would_cycle(&tasks, 1, 3));
+    }
+}
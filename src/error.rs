@@ -0,0 +1,93 @@
+use std::fmt;
+use std::io;
+use std::path::PathBuf;
+
+use crate::storage::StorageError;
+
+/// Process exit codes, one per error category.
+///
+/// `2` is left to clap for command-line usage errors.
+pub mod exit_code {
+    pub const FAILURE: i32 = 1;
+    pub const NOT_FOUND: i32 = 3;
+    pub const INVALID_INPUT: i32 = 4;
+    pub const STORAGE: i32 = 5;
+    pub const GIT: i32 = 6;
+}
+
+#[derive(Debug)]
+pub enum PmError {
+    TaskNotFound(u32),
+    InvalidInput(String),
+    Storage(StorageError),
+    Git(String),
+    Io(io::Error),
+    /// A rejected `edit-task --editor` edit, still saved in `path`.
+    EditKept {
+        path: PathBuf,
+        source: Box<PmError>,
+    },
+}
+
+pub type Result<T> = std::result::Result<T, PmError>;
+
+impl PmError {
+    pub fn exit_code(&self) -> i32 {
+        match self {
+            PmError::TaskNotFound(_) => exit_code::NOT_FOUND,
+            PmError::InvalidInput(_) => exit_code::INVALID_INPUT,
+            PmError::Storage(StorageError::ProjectNotFound(_)) => exit_code::NOT_FOUND,
+            PmError::Storage(StorageError::InvalidName(_) | StorageError::ProjectExists(_)) => {
+                exit_code::INVALID_INPUT
+            }
+            PmError::Storage(_) => exit_code::STORAGE,
+            PmError::Git(_) => exit_code::GIT,
+            PmError::Io(_) => exit_code::FAILURE,
+            PmError::EditKept { source, .. } => source.exit_code(),
+        }
+    }
+}
+
+impl fmt::Display for PmError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            PmError::TaskNotFound(id) => write!(f, "task with id {} not found", id),
+            PmError::InvalidInput(msg) => write!(f, "{}", msg),
+            PmError::Storage(e) => write!(f, "{}", e),
+            PmError::Git(msg) => write!(f, "git: {}", msg),
+            PmError::Io(e) => write!(f, "{}", e),
+            PmError::EditKept { path, source } => {
+                write!(f, "{} (your edit is kept in {})", source, path.display())
+            }
+        }
+    }
+}
+
+impl std::error::Error for PmError {
+    fn source(&self) -> Option<&(dyn std::error::Error + 'static)> {
+        match self {
+            PmError::Storage(e) => Some(e),
+            PmError::Io(e) => Some(e),
+            PmError::EditKept { source, .. } => Some(source.as_ref()),
+            _ => None,
+        }
+    }
+}
+
+impl From<StorageError> for PmError {
+    fn from(e: StorageError) -> Self {
+        PmError::Storage(e)
+    }
+}
+
+impl From<git2::Error> for PmError {
+    fn from(e: git2::Error) -> Self {
+        PmError::Git(e.message().to_string())
+    }
+}
+
+impl From<io::Error> for PmError {
+    fn from(e: io::Error) -> Self {
+        PmError::Io(e)
+    }
+}
@@ -0,0 +1,101 @@
+use chrono::{DateTime, Local};
+use serde::{Deserialize, Serialize};
+use std::fmt;
+
+use crate::models::Task;
+
+/// One line of a project's append-only `history.jsonl`.
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
+pub struct HistoryEntry {
+    pub task: u32,
+    pub at: DateTime<Local>,
+    /// Login name of whoever made the change.
+    pub user: String,
+    pub change: Change,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub from: Option<String>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub to: Option<String>,
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone, Copy, PartialEq, Eq)]
+#[serde(rename_all = "lowercase")]
+pub enum Change {
+    Created,
+    Status,
+    Priority,
+    Owner,
+    Deleted,
+}
+
+impl fmt::Display for Change {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let name = match self {
+            Change::Created => "created",
+            Change::Status => "status",
+            Change::Priority => "priority",
+            Change::Owner => "owner",
+            Change::Deleted => "deleted",
+        };
+        f.write_str(name)
+    }
+}
+
+/// Entries describing how `old` became `new` (`None` for a new task).
+pub fn changes(
+    old: Option<&Task>,
+    new: &Task,
+    user: &str,
+    at: DateTime<Local>,
+) -> Vec<HistoryEntry> {
+    let entry = |change, from: Option<String>, to: Option<String>| HistoryEntry {
+        task: new.id,
+        at,
+        user: user.to_string(),
+        change,
+        from,
+        to,
+    };
+
+    let Some(old) = old else {
+        return vec![entry(Change::Created, None, Some(new.status.to_string()))];
+    };
+
+    let mut entries = Vec::new();
+
+    if old.status != new.status {
+        entries.push(entry(
+            Change::Status,
+            Some(old.status.to_string()),
+            Some(new.status.to_string()),
+        ));
+    }
+    if old.priority != new.priority {
+        entries.push(entry(
+            Change::Priority,
+            Some(old.priority.to_string()),
+            Some(new.priority.to_string()),
+        ));
+    }
+    if old.owner != new.owner {
+        entries.push(entry(
+            Change::Owner,
+            Some(old.owner.clone()),
+            Some(new.owner.clone()),
+        ));
+    }
+
+    entries
+}
+
+/// Entry recording that `task` was deleted.
+pub fn deleted(task: &Task, user: &str, at: DateTime<Local>) -> HistoryEntry {
+    HistoryEntry {
+        task: task.id,
+        at,
+        user: user.to_string(),
+        change: Change::Deleted,
+        from: Some(task.status.to_string()),
+        to: None,
+    }
+}
@@ -0,0 +1,204 @@
+//! iCalendar (RFC 5545) output of task deadlines.
+
+use chrono::{DateTime, Local, TimeZone, Utc};
+
+use crate::models::{Priority, StatusKind, Task};
+use crate::workflow::Workflow;
+
+/// A calendar with one all-day VTODO per task that has a deadline. Tasks
+/// keep their UID between exports, so calendar apps update them instead of
+/// adding duplicates.
+pub fn calendar(project: &str, tasks: &[Task], workflow: &Workflow) -> String {
+    let now = Local::now();
+    let mut out = String::new();
+
+    line(&mut out, "BEGIN:VCALENDAR");
+    line(&mut out, "VERSION:2.0");
+    line(
+        &mut out,
+        &format!("PRODID:-//pmcli//pmcli {}//EN", env!("CARGO_PKG_VERSION")),
+    );
+    line(&mut out, "CALSCALE:GREGORIAN");
+    line(&mut out, &format!("X-WR-CALNAME:{}", text(project)));
+
+    for task in tasks {
+        let Some(deadline) = task.deadline else {
+            continue;
+        };
+
+        line(&mut out, "BEGIN:VTODO");
+        line(&mut out, &format!("UID:{}", uid(project, task.id)));
+        line(
+            &mut out,
+            &format!("DTSTAMP:{}", timestamp(task.updated_at.unwrap_or(now))),
+        );
+        line(&mut out, &format!("SUMMARY:{}", text(&task.description)));
+        line(
+            &mut out,
+            &format!("DUE;VALUE=DATE:{}", deadline.format("%Y%m%d")),
+        );
+        line(
+            &mut out,
+            &format!("STATUS:{}", status(workflow.kind(&task.status))),
+        );
+        line(&mut out, &format!("PRIORITY:{}", priority(task.priority)));
+
+        if !task.tags.is_empty() {
+            let tags: Vec<String> = task.tags.iter().map(|t| text(t)).collect();
+            line(&mut out, &format!("CATEGORIES:{}", tags.join(",")));
+        }
+        if let Some(created) = task.created_at {
+            line(&mut out, &format!("CREATED:{}", timestamp(created)));
+        }
+        if let Some(updated) = task.updated_at {
+            line(&mut out, &format!("LAST-MODIFIED:{}", timestamp(updated)));
+        }
+        if let Some(completed) = task.completed_at {
+            line(&mut out, &format!("COMPLETED:{}", timestamp(completed)));
+        }
+
+        line(&mut out, "END:VTODO");
+    }
+
+    line(&mut out, "END:VCALENDAR");
+    out
+}
+
+/// Unique and stable per project and task ID.
+pub fn uid(project: &str, id: u32) -> String {
+    format!("{}-{}@pmcli", id, project)
+}
+
+/// VTODO has no blocked state, so blocked tasks still need action.
+fn status(kind: StatusKind) -> &'static str {
+    match kind {
+        StatusKind::Todo | StatusKind::Blocked => "NEEDS-ACTION",
+        StatusKind::Active => "IN-PROCESS",
+        StatusKind::Done => "COMPLETED",
+        StatusKind::Cancelled => "CANCELLED",
+    }
+}
+
+/// iCalendar priorities run from 1 (highest) to 9 (lowest); 0 means
+/// undefined, so level 0 maps to the lowest instead.
+fn priority(priority: Priority) -> u8 {
+    10 - priority.level().clamp(1, 9)
+}
+
+fn timestamp<Tz: TimeZone>(at: DateTime<Tz>) -> String {
+    at.with_timezone(&Utc).format("%Y%m%dT%H%M%SZ").to_string()
+}
+
+/// Escape a TEXT value.
+fn text(s: &str) -> String {
+    s.replace('\\', "\\\\")
+        .replace(';', "\\;")
+        .replace(',', "\\,")
+        .replace("\r\n", "\\n")
+        .replace(['\r', '\n'], "\\n")
+}
+
+/// Append a content line, folded at 75 octets as the RFC requires.
+fn line(out: &mut String, content: &str) {
+    let mut width = 0;
+
+    for c in content.chars() {
+        if width + c.len_utf8() > 75 {
+            out.push_str("\r\n ");
+            width = 1;
+        }
+        out.push(c);
+        width += c.len_utf8();
+    }
+
+    out.push_str("\r\n");
+}
+
+#[cfg(test)]
+mod tests {
+    use chrono::NaiveDate;
+
+    use super::*;
+    use crate::models::Status;
+    use crate::test_support;
+
+    fn task(id: u32, status: Status, deadline: Option<&str>) -> Task {
+        Task {
+            description: "Ship v2, then; relax".into(),
+            priority: Priority::High,
+            deadline: deadline.map(|d| d.parse().unwrap()),
+            tags: vec!["api".into(), "a,b".into()],
+            ..test_support::task(id, status)
+        }
+    }
+
+    #[test]
+    fn escapes_text_values() {
+        assert_eq!(text("a,b;c\\d\r\ne\nf"), r"a\,b\;c\\d\ne\nf");
+    }
+
+    #[test]
+    fn folds_long_lines_between_characters() {
+        let mut out = String::new();
+        let content = format!("SUMMARY:{}", "é".repeat(60));
+
+        line(&mut out, &content);
+
+        let lines: Vec<&str> = out.strip_suffix("\r\n").unwrap().split("\r\n").collect();
+        assert!(lines.len() > 1);
+        assert!(lines.iter().all(|l| l.len() <= 75));
+        assert!(lines[1..].iter().all(|l| l.starts_with(' ')));
+        assert_eq!(out.replace("\r\n ", ""), format!("{}\r\n", content));
+
+        let mut short = String::new();
+        line(&mut short, &"x".repeat(75));
+        assert_eq!(short, format!("{}\r\n", "x".repeat(75)));
+    }
+
+    #[test]
+    fn maps_priorities_and_statuses() {
+        assert_eq!(priority(Priority::Urgent), 1);
+        assert_eq!(priority(Priority::High), 3);
+        assert_eq!(priority(Priority::Medium), 5);
+        assert_eq!(priority(Priority::Low), 7);
+        assert_eq!(priority(Priority::Level(0)), 9);
+
+        assert_eq!(status(StatusKind::Todo), "NEEDS-ACTION");
+        assert_eq!(status(StatusKind::Blocked), "NEEDS-ACTION");
+        assert_eq!(status(StatusKind::Active), "IN-PROCESS");
+        assert_eq!(status(StatusKind::Done), "COMPLETED");
+        assert_eq!(status(StatusKind::Cancelled), "CANCELLED");
+    }
+
+    #[test]
+    fn writes_one_todo_per_deadline() {
+        let tasks = [
+            task(1, Status::Todo, Some("2024-04-02")),
+            task(2, Status::Done, None),
+            task(3, Status::InProgress, Some("2024-04-03")),
+        ];
+
+        let ics = calendar("web", &tasks, &Workflow::default());
+
+        assert!(ics.starts_with("BEGIN:VCALENDAR\r\nVERSION:2.0\r\n"));
+        assert!(ics.ends_with("END:VTODO\r\nEND:VCALENDAR\r\n"));
+        assert_eq!(ics.matches("BEGIN:VTODO").count(), 2);
+        assert!(ics.contains("UID:1-web@pmcli\r\n"));
+        assert!(!ics.contains("UID:2-web@pmcli"));
+        assert!(ics.contains("DUE;VALUE=DATE:20240402\r\n"));
+        assert!(ics.contains("STATUS:IN-PROCESS\r\n"));
+        assert!(ics.contains(r"SUMMARY:Ship v2\, then\; relax"));
+        assert!(ics.contains("CATEGORIES:api,a\\,b\r\n"));
+        assert!(!ics.replace("\r\n", "").contains('\n'));
+        assert_eq!(
+            timestamp(
+                NaiveDate::from_ymd_opt(2024, 3, 1)
+                    .unwrap()
+                    .and_hms_opt(9, 30, 0)
+                    .unwrap()
+                    .and_utc()
+            ),
+            "20240301T093000Z"
+        );
+    }
+}
@@ -0,0 +1,230 @@
+use chrono::{DateTime, Local};
+use serde::{Deserialize, Serialize};
+
+use crate::error::{PmError, Result};
+use crate::models::Task;
+
+/// How many operations `journal.json` keeps for undo.
+const LIMIT: usize = 50;
+
+/// Undo and redo stacks of a project, stored as `journal.json`.
+#[derive(Serialize, Deserialize, Debug, Clone, Default)]
+pub struct Journal {
+    #[serde(default)]
+    pub undo: Vec<JournalEntry>,
+    #[serde(default)]
+    pub redo: Vec<JournalEntry>,
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone)]
+pub struct JournalEntry {
+    pub at: DateTime<Local>,
+    pub operation: Operation,
+}
+
+/// A recorded mutation, in the direction it was originally made.
+#[derive(Serialize, Deserialize, Debug, Clone)]
+#[serde(tag = "kind", rename_all = "lowercase")]
+pub enum Operation {
+    Tasks { changes: Vec<TaskChange> },
+    Note { text: String },
+}
+
+/// One task before and after an operation; `None` when it did not exist.
+///
+/// Archived tasks are recognised by their `archived_at`.
+#[derive(Serialize, Deserialize, Debug, Clone)]
+pub struct TaskChange {
+    pub id: u32,
+    pub before: Option<Task>,
+    pub after: Option<Task>,
+}
+
+impl Journal {
+    /// Push a new operation. Anything that could be redone is dropped.
+    pub fn record(&mut self, operation: Operation) {
+        self.undo.push(JournalEntry {
+            at: Local::now(),
+            operation,
+        });
+        self.redo.clear();
+
+        if self.undo.len() > LIMIT {
+            self.undo.drain(..self.undo.len() - LIMIT);
+        }
+    }
+}
+
+impl Operation {
+    /// Short description for messages, e.g. `task 3: todo → done`.
+    pub fn summary(&self) -> String {
+        let changes = match self {
+            Operation::Note { text } => return format!("note \"{}\"", text),
+            Operation::Tasks { changes } => changes,
+        };
+
+        let [change] = changes.as_slice() else {
+            return format!("changes to {} tasks", changes.len());
+        };
+
+        match (&change.before, &change.after) {
+            (None, Some(_)) => format!("add task {}", change.id),
+            (Some(_), None) => format!("delete task {}", change.id),
+            (Some(before), Some(after))
+                if before.archived_at.is_none() != after.archived_at.is_none() =>
+            {
+                match after.archived_at {
+                    Some(_) => format!("archive task {}", change.id),
+                    None => format!("restore task {}", change.id),
+                }
+            }
+            (Some(before), Some(after)) if before.status != after.status => {
+                format!("task {}: {} → {}", change.id, before.status, after.status)
+            }
+            _ => format!("edit task {}", change.id),
+        }
+    }
+}
+
+/// Put every task of `changes` back to its `before` state (`undo`) or
+/// forward to its `after` state.
+///
+/// Fails without touching anything when a task no longer matches the state
+/// the operation left it in, e.g. because it was edited since.
+pub(crate) fn apply(
+    tasks: &mut Vec<Task>,
+    archive: &mut Vec<Task>,
+    changes: &[TaskChange],
+    undo: bool,
+) -> Result<()> {
+    let find = |id: u32| tasks.iter().chain(archive.iter()).find(|t| t.id == id);
+
+    for change in changes {
+        let expected = if undo { &change.after } else { &change.before };
+
+        if find(change.id) != expected.as_ref() {
+            return Err(PmError::InvalidInput(format!(
+                "task {} changed since, cannot {}",
+                change.id,
+                if undo { "undo" } else { "redo" }
+            )));
+        }
+    }
+
+    for change in changes {
+        tasks.retain(|t| t.id != change.id);
+        archive.retain(|t| t.id != change.id);
+
+        let target = if undo { &change.before } else { &change.after };
+
+        if let Some(task) = target {
+            let list = if task.archived_at.is_some() {
+                &mut *archive
+            } else {
+                &mut *tasks
+            };
+            let pos = list.partition_point(|t| t.id < task.id);
+            list.insert(pos, task.clone());
+        }
+    }
+
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::models::Status;
+    use crate::test_support::task;
+
+    fn change(id: u32, before: Option<Task>, after: Option<Task>) -> TaskChange {
+        TaskChange { id, before, after }
+    }
+
+    #[test]
+    fn undo_and_redo_restore_each_side() {
+        let mut archived = task(2, Status::Done);
+        archived.archived_at = Some(Local::now());
+        let changes = [
+            change(1, Some(task(1, Status::Todo)), Some(task(1, Status::Done))),
+            change(2, Some(task(2, Status::Done)), Some(archived.clone())),
+            change(3, None, Some(task(3, Status::Todo))),
+        ];
+        let mut tasks = vec![task(1, Status::Done), task(3, Status::Todo)];
+        let mut archive = vec![archived.clone()];
+
+        apply(&mut tasks, &mut archive, &changes, true).unwrap();
+        assert_eq!(tasks, [task(1, Status::Todo), task(2, Status::Done)]);
+        assert!(archive.is_empty());
+
+        apply(&mut tasks, &mut archive, &changes, false).unwrap();
+        assert_eq!(tasks, [task(1, Status::Done), task(3, Status::Todo)]);
+        assert_eq!(archive, [archived]);
+    }
+
+    #[test]
+    fn refuses_tasks_changed_since() {
+        let changes = [
+            change(1, Some(task(1, Status::Todo)), Some(task(1, Status::Done))),
+            change(2, Some(task(2, Status::Todo)), None),
+        ];
+        let mut tasks = vec![task(1, Status::Done), task(2, Status::Blocked)];
+
+        let err = apply(&mut tasks, &mut Vec::new(), &changes, true).unwrap_err();
+
+        assert_eq!(err.to_string(), "task 2 changed since, cannot undo");
+        assert_eq!(tasks, [task(1, Status::Done), task(2, Status::Blocked)]);
+    }
+
+    #[test]
+    fn keeps_the_latest_operations() {
+        let mut journal = Journal::default();
+        journal.redo.push(JournalEntry {
+            at: Local::now(),
+            operation: Operation::Note { text: "x".into() },
+        });
+
+        for i in 0..LIMIT + 5 {
+            journal.record(Operation::Note {
+                text: i.to_string(),
+            });
+        }
+
+        assert!(journal.redo.is_empty());
+        assert_eq!(journal.undo.len(), LIMIT);
+        assert_eq!(journal.undo[0].operation.summary(), "note \"5\"");
+    }
+
+    #[test]
+    fn summarizes_operations() {
+        let summary = |changes: Vec<TaskChange>| Operation::Tasks { changes }.summary();
+
+        assert_eq!(
+            summary(vec![change(4, None, Some(task(4, Status::Todo)))]),
+            "add task 4"
+        );
+        assert_eq!(
+            summary(vec![change(
+                4,
+                Some(task(4, Status::Todo)),
+                Some(task(4, Status::Done))
+            )]),
+            "task 4: todo → done"
+        );
+        assert_eq!(
+            summary(vec![change(
+                4,
+                Some(task(4, Status::InProgress)),
+                Some(task(4, Status::Cancelled))
+            )]),
+            "task 4: in-progress → cancelled"
+        );
+        assert_eq!(
+            summary(vec![
+                change(4, Some(task(4, Status::Todo)), None),
+                change(5, None, None)
+            ]),
+            "changes to 2 tasks"
+        );
+    }
+}
@@ -0,0 +1,45 @@
+//! pmcli as a library.
+//!
+//! The `pmcli` binary is a thin clap front end over this crate; other Rust
+//! tools can drive projects the same way without shelling out:
+//!
+//! ```no_run
+//! use pmcli::commands;
+//!
+//! let task = commands::add_task::run("web", "Write docs", Some("high"), None, None, &[])?;
+//! println!("added #{}", task.id);
+//!
+//! let query = commands::tasks::TaskQuery {
+//!     filter: Some("open".into()),
+//!     tags: vec!["docs".into()],
+//!     ..Default::default()
+//! };
+//!
+//! for task in commands::tasks::run("web", &query)? {
+//!     println!("{} {}", task.id, task.description);
+//! }
+//! # Ok::<(), pmcli::error::PmError>(())
+//! ```
+//!
+//! Command functions return data and never print; lower-level access to the
+//! project files is available through [`storage::ProjectStore`].
+
+pub mod bundle;
+pub mod commands;
+pub mod config;
+pub mod csv;
+pub mod deps;
+pub mod error;
+pub mod history;
+pub mod ical;
+pub mod journal;
+pub mod models;
+pub mod query;
+pub mod report;
+pub mod storage;
+pub mod tags;
+pub mod taskwarrior;
+#[cfg(test)]
+pub(crate) mod test_support;
+pub mod tree;
+pub mod workflow;
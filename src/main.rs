@@ -1,18 +1,70 @@
-use clap::{Parser, Subcommand};
+use clap::{Parser, Subcommand, ValueEnum};
+use colored::Colorize;
+use serde::Serialize;
+use std::io::{self, IsTerminal, Write};
+use std::path::PathBuf;
+use std::process;
+use std::sync::atomic::{AtomicBool, Ordering};
 
-mod commands;
-mod config;
-mod models;
+use pmcli::commands::{
+    self,
+    bulk::BulkAction,
+    edit_task::TaskEdit,
+    export::{Column, ExportFormat, ExportOptions},
+    git::SyncAuth,
+    import::{ImportOptions, ImportSource},
+    import_csv::CsvImportOptions,
+    tasks::{SortKey, TaskQuery, TreeRow},
+};
+use pmcli::error::{PmError, Result};
+use pmcli::models::{StatusKind, Task};
+use pmcli::storage;
+
+/// Set by `--no-emoji`.
+static NO_EMOJI: AtomicBool = AtomicBool::new(false);
+
+/// `println!` for messages that start with an emoji, which `--no-emoji`
+/// leaves out.
+macro_rules! say {
+    ($($arg:tt)*) => {
+        println!("{}", without_emoji(&format!($($arg)*)))
+    };
+}
 
 #[derive(Parser)]
 #[command(name = "pmcli")]
 #[command(about = "Project Manager CLI for Termux")]
 #[command(version)]
 struct Cli {
+    /// Output format of list, tasks, tags, task-log and workflow, and of the
+    /// tasks changed by task commands and bulk
+    #[arg(long, global = true, value_enum, default_value_t = OutputFormat::Table)]
+    output: OutputFormat,
+
+    /// No colours (also off when NO_COLOR is set or output is not a terminal)
+    #[arg(long, global = true)]
+    no_color: bool,
+
+    /// No emoji in messages
+    #[arg(long, global = true)]
+    no_emoji: bool,
+
     #[command(subcommand)]
     command: Commands,
 }
 
+#[derive(Clone, Copy, PartialEq, Eq, ValueEnum)]
+enum OutputFormat {
+    /// Aligned text
+    Table,
+    /// Tab-separated fields, one record per line, no decoration
+    Plain,
+    /// A JSON array
+    Json,
+    /// One JSON object per line
+    Ndjson,
+}
+
 #[derive(Subcommand)]
 enum Commands {
     /// Create new project
@@ -23,26 +75,204 @@ enum Commands {
         project: String,
         task: String,
 
-        /// Task priority (low | medium | high)
+        /// Task priority: low | medium | high | urgent, or 0-9
         #[arg(short, long)]
         priority: Option<String>,
 
         /// Deadline (uses config date_format)
         #[arg(short, long)]
         deadline: Option<String>,
+
+        /// Add as a subtask of this task ID
+        #[arg(long)]
+        parent: Option<u32>,
+
+        /// Tag the task (repeatable)
+        #[arg(short, long = "tag")]
+        tags: Vec<String>,
+    },
+
+    /// Edit an existing task
+    EditTask {
+        project: String,
+        id: u32,
+
+        /// New description
+        #[arg(long)]
+        description: Option<String>,
+
+        /// New priority: low | medium | high | urgent, or 0-9
+        #[arg(short, long)]
+        priority: Option<String>,
+
+        /// New deadline (uses config date_format)
+        #[arg(short, long, conflicts_with = "clear_deadline")]
+        deadline: Option<String>,
+
+        /// New owner
+        #[arg(short, long)]
+        owner: Option<String>,
+
+        /// Remove the deadline
+        #[arg(long)]
+        clear_deadline: bool,
+
+        /// Edit the task as TOML in $EDITOR
+        #[arg(
+            short,
+            long,
+            conflicts_with_all = ["description", "priority", "deadline", "owner", "clear_deadline"]
+        )]
+        editor: bool,
+
+        /// With --editor, complete the task even when it has open subtasks
+        #[arg(long, requires = "editor")]
+        force: bool,
     },
 
     /// Show tasks
     Tasks {
         project: String,
 
-        /// Filter: mine | todo | done | blocked
+        /// Filter expression, e.g. `status:todo and tag:api`, or `@name` for a saved query
+        query: Vec<String>,
+
+        /// Filter: mine | open | ready | todo | in-progress | blocked | done | cancelled
         #[arg(short, long)]
         filter: Option<String>,
+
+        /// Only tasks with this status (repeatable); tasks waiting on a dependency count as blocked
+        #[arg(long = "status")]
+        statuses: Vec<String>,
+
+        /// Only tasks of this owner (`me` for yourself)
+        #[arg(short, long)]
+        owner: Option<String>,
+
+        /// Only tasks with this priority
+        #[arg(short, long)]
+        priority: Option<String>,
+
+        /// Only tasks due on or before this date
+        #[arg(long)]
+        due_before: Option<String>,
+
+        /// Only tasks due on or after this date
+        #[arg(long)]
+        due_after: Option<String>,
+
+        /// Only open tasks past their deadline
+        #[arg(long)]
+        overdue: bool,
+
+        /// Only tasks whose description contains this text
+        #[arg(long)]
+        text: Option<String>,
+
+        /// Only tasks with this tag, or without it when prefixed with `!`
+        /// (repeatable)
+        #[arg(short, long = "tag")]
+        tags: Vec<String>,
+
+        /// Order: id | priority | deadline | status | created
+        #[arg(short, long, default_value = "id")]
+        sort: SortKey,
+
+        /// Reverse the order
+        #[arg(short, long)]
+        reverse: bool,
+
+        /// Show at most this many tasks
+        #[arg(short, long)]
+        limit: Option<usize>,
+
+        /// Also show archived tasks
+        #[arg(long)]
+        include_archived: bool,
     },
 
     /// Mark task as done
-    DoneTask { project: String, id: u32 },
+    DoneTask {
+        project: String,
+        id: u32,
+
+        /// Complete it even if subtasks are still open
+        #[arg(long)]
+        force: bool,
+    },
+
+    /// Change task status
+    SetStatus {
+        project: String,
+        id: u32,
+
+        /// Status name from the project workflow
+        /// (default: todo | in-progress | blocked | done | cancelled)
+        status: String,
+
+        /// Complete it even if subtasks are still open
+        #[arg(long)]
+        force: bool,
+    },
+
+    /// Put a done or cancelled task back to todo
+    ReopenTask { project: String, id: u32 },
+
+    /// Show the change history of a task
+    TaskLog { project: String, id: u32 },
+
+    /// Undo the last change to a project
+    Undo { project: String },
+
+    /// Redo the last undone change
+    Redo { project: String },
+
+    /// Show the project workflow (statuses and allowed transitions)
+    Workflow {
+        project: String,
+
+        /// Write the default workflow to workflow.toml for editing
+        #[arg(long)]
+        init: bool,
+    },
+
+    /// Add tags to a task
+    Tag {
+        project: String,
+        id: u32,
+        #[arg(required = true)]
+        tags: Vec<String>,
+    },
+
+    /// Remove tags from a task
+    Untag {
+        project: String,
+        id: u32,
+        #[arg(required = true)]
+        tags: Vec<String>,
+    },
+
+    /// List the tags used in a project with their task counts
+    Tags { project: String },
+
+    /// Make a task depend on another one
+    Link {
+        project: String,
+        id: u32,
+
+        /// Task that must be done first
+        depends_on: u32,
+    },
+
+    /// Remove a dependency between two tasks
+    Unlink {
+        project: String,
+        id: u32,
+        depends_on: u32,
+    },
+
+    /// Print the task dependency graph in Graphviz DOT format
+    Graph { project: String },
 
     /// List all projects
     List,
@@ -50,11 +280,140 @@ enum Commands {
     /// Add project note
     Note { project: String, note: String },
 
-    /// Export project data
-    Export { project: String },
+    /// Renumber duplicate task IDs (all projects if none given)
+    Repair { project: Option<String> },
+
+    /// Export tasks as CSV, iCalendar or Taskwarrior JSON, a project report as
+    /// Markdown or HTML, or the whole project as a bundle
+    Export {
+        project: String,
+
+        /// Only export tasks matching this filter expression
+        query: Vec<String>,
+
+        /// Format: csv | markdown | html | ics | bundle | taskwarrior
+        #[arg(short, long, default_value = "csv")]
+        format: ExportFormat,
+
+        /// CSV columns to write, comma-separated (default: all)
+        #[arg(long, value_delimiter = ',')]
+        columns: Vec<Column>,
+
+        /// CSV field separator, e.g. ';' or 'tab'
+        #[arg(long, default_value = ",", value_parser = parse_delimiter)]
+        delimiter: char,
+
+        /// Write to this file instead of standard output
+        #[arg(long, value_name = "PATH")]
+        file: Option<PathBuf>,
+
+        /// Also export archived tasks
+        #[arg(long)]
+        include_archived: bool,
+    },
+
+    /// Import a project from a bundle written by `export --format bundle`, or
+    /// projects from a Taskwarrior export
+    Import {
+        file: PathBuf,
+
+        /// Format of the file: bundle | taskwarrior
+        #[arg(long, default_value = "bundle")]
+        from: ImportSource,
+
+        /// Import under this project name instead of the bundled one
+        #[arg(long = "as", value_name = "NAME")]
+        name: Option<String>,
+
+        /// Replace the project if it already exists
+        #[arg(long, conflicts_with = "merge")]
+        force: bool,
+
+        /// Add the tasks and notes to the project if it already exists
+        #[arg(long)]
+        merge: bool,
+    },
+
+    /// Add tasks from a CSV file, e.g. one written by `export` or a spreadsheet
+    ImportCsv {
+        project: String,
+
+        file: PathBuf,
+
+        /// Read a task field from a differently named column, e.g.
+        /// `description=Title` (repeatable)
+        #[arg(long, value_name = "FIELD=HEADER", value_parser = parse_mapping)]
+        map: Vec<(Column, String)>,
+
+        /// Field separator, e.g. ';' or 'tab'
+        #[arg(long, default_value = ",", value_parser = parse_delimiter)]
+        delimiter: char,
+
+        /// Import nothing if any row has an error
+        #[arg(long)]
+        strict: bool,
+    },
 
     /// Terminal UI
-    Tui { project: String },
+    Tui {
+        project: String,
+
+        /// Also show archived tasks
+        #[arg(long)]
+        include_archived: bool,
+    },
+
+    /// Permanently delete a task
+    DeleteTask { project: String, id: u32 },
+
+    /// Move tasks to the project archive
+    ArchiveTask {
+        project: String,
+
+        /// IDs of the tasks to archive
+        #[arg(required_unless_present = "done_older_than")]
+        ids: Vec<u32>,
+
+        /// Archive all Done tasks completed more than N days ago
+        #[arg(long, value_name = "DAYS", conflicts_with = "ids")]
+        done_older_than: Option<u32>,
+    },
+
+    /// Move an archived task back to the task list
+    RestoreTask { project: String, id: u32 },
+
+    /// Change all tasks matching a query at once
+    Bulk {
+        project: String,
+
+        /// Filter expression selecting the tasks, e.g. `status:review tag:sprint-4`
+        #[arg(required = true)]
+        query: Vec<String>,
+
+        /// Move the tasks to this status
+        #[arg(long, value_name = "STATUS")]
+        set_status: Option<String>,
+
+        /// Assign the tasks to this owner
+        #[arg(long, value_name = "OWNER")]
+        set_owner: Option<String>,
+
+        /// Add a tag to the tasks (repeatable)
+        #[arg(long = "add-tag", value_name = "TAG")]
+        add_tags: Vec<String>,
+
+        /// Move the tasks to the archive
+        #[arg(long)]
+        archive: bool,
+
+        /// Complete tasks even when they have open subtasks
+        #[arg(long)]
+        force: bool,
+
+        /// Only list the tasks that would change
+        #[arg(long)]
+        dry_run: bool,
+    },
 
     // ===== GIT SYNC =====
     /// Initialize git repository
@@ -73,34 +432,805 @@ enum Commands {
 fn main() {
     let cli = Cli::parse();
 
-    match cli.command {
-        Commands::Create { name } => commands::create::run(&name),
+    NO_EMOJI.store(cli.no_emoji, Ordering::Relaxed);
+    if cli.no_color || !io::stdout().is_terminal() {
+        colored::control::set_override(false);
+    }
+
+    match run(cli.command, cli.output) {
+        Ok(()) => {}
+        // Output piped into `head` and the like
+        Err(PmError::Io(e)) if e.kind() == io::ErrorKind::BrokenPipe => {}
+        Err(e) => {
+            eprintln!("{}", without_emoji(&format!("❌ {}", e)));
+            process::exit(e.exit_code());
+        }
+    }
+}
+
+fn run(command: Commands, output: OutputFormat) -> Result<()> {
+    if output != OutputFormat::Table {
+        if let Some(name) = without_records(&command) {
+            return Err(PmError::InvalidInput(format!(
+                "--output does not apply to {}",
+                name
+            )));
+        }
+    }
+
+    match command {
+        Commands::Create { name } => {
+            commands::create::run(&name)?;
+            say!("✅ Project '{}' created", name);
+        }
 
         Commands::AddTask {
             project,
             task,
             priority,
             deadline,
-        } => commands::add_task::run(&project, &task, priority.as_deref(), deadline.as_deref()),
+            parent,
+            tags,
+        } => {
+            let task = commands::add_task::run(
+                &project,
+                &task,
+                priority.as_deref(),
+                deadline.as_deref(),
+                parent,
+                &tags,
+            )?;
+            if output != OutputFormat::Table {
+                return print_tasks(&[task], output);
+            }
+            say!("➕ Task added");
+        }
+
+        Commands::EditTask {
+            project,
+            id,
+            description,
+            priority,
+            deadline,
+            owner,
+            clear_deadline,
+            editor,
+            force,
+        } => {
+            let updated = if editor {
+                commands::edit_task::run_editor(&project, id, force)?
+            } else {
+                let edit = TaskEdit {
+                    description,
+                    priority,
+                    deadline,
+                    owner,
+                    clear_deadline,
+                };
+                Some(commands::edit_task::run(&project, id, &edit)?)
+            };
+
+            if output != OutputFormat::Table {
+                return print_tasks(updated.as_slice(), output);
+            }
+
+            match updated {
+                Some(task) => say!("✏️  Task {} updated", task.id),
+                None => println!("No changes"),
+            }
+        }
+
+        Commands::Tasks {
+            project,
+            query,
+            filter,
+            statuses,
+            owner,
+            priority,
+            due_before,
+            due_after,
+            overdue,
+            text,
+            tags,
+            sort,
+            reverse,
+            limit,
+            include_archived,
+        } => {
+            let query = TaskQuery {
+                query: join_query(query),
+                filter,
+                statuses,
+                owner,
+                priority,
+                due_before,
+                due_after,
+                overdue,
+                text,
+                tags,
+                sort,
+                reverse,
+                limit,
+                include_archived,
+            };
+
+            let rows = commands::tasks::run_tree(&project, &query)?;
+
+            if output != OutputFormat::Table {
+                let tasks: Vec<_> = rows.into_iter().map(|r| r.task).collect();
+                return print_tasks(&tasks, output);
+            }
+
+            rows.iter().for_each(print_row);
+        }
+
+        Commands::DoneTask { project, id, force } => {
+            let task = commands::done_task::run(&project, id, force)?;
+            if output != OutputFormat::Table {
+                return print_tasks(&[task], output);
+            }
+            say!("✅ Task marked as {}", task.status.label());
+        }
+
+        Commands::SetStatus {
+            project,
+            id,
+            status,
+            force,
+        } => {
+            let task = commands::set_status::run(&project, id, &status, force)?;
+            if output != OutputFormat::Table {
+                return print_tasks(&[task], output);
+            }
+            say!("🔄 Task {} is now {}", task.id, task.status.label());
+        }
+
+        Commands::ReopenTask { project, id } => {
+            let task = commands::set_status::run_reopen(&project, id)?;
+            if output != OutputFormat::Table {
+                return print_tasks(&[task], output);
+            }
+            say!("🔄 Task {} reopened", id);
+        }
+
+        Commands::TaskLog { project, id } => {
+            let entries = commands::task_log::run(&project, id)?;
+
+            if output != OutputFormat::Table {
+                return print_records(&entries, output, |e| {
+                    vec![
+                        e.at.to_rfc3339(),
+                        e.user.clone(),
+                        e.change.to_string(),
+                        e.from.clone().unwrap_or_default(),
+                        e.to.clone().unwrap_or_default(),
+                    ]
+                });
+            }
+
+            if entries.is_empty() {
+                say!("📭 No history recorded for task {}", id);
+            }
+
+            for e in entries {
+                let change = match (&e.from, &e.to) {
+                    (Some(from), Some(to)) => format!("{} → {}", from, to),
+                    (None, Some(to)) => to.clone(),
+                    (Some(from), None) => from.clone(),
+                    (None, None) => String::new(),
+                };
+                println!(
+                    "{}  {:<10} {:<8} {}",
+                    e.at.format("%Y-%m-%d %H:%M"),
+                    e.user,
+                    e.change,
+                    change
+                );
+            }
+        }
 
-        Commands::Tasks { project, filter } => commands::tasks::run(&project, filter.as_deref()),
+        Commands::Undo { project } => match commands::undo::run(&project)? {
+            Some(entry) => say!("↩️  Undid {}", entry.operation.summary()),
+            None => say!("📭 Nothing to undo"),
+        },
 
-        Commands::DoneTask { project, id } => commands::done_task::run(&project, id),
+        Commands::Redo { project } => match commands::undo::run_redo(&project)? {
+            Some(entry) => say!("↪️  Redid {}", entry.operation.summary()),
+            None => say!("📭 Nothing to redo"),
+        },
+
+        Commands::Workflow {
+            project,
+            init: true,
+        } => {
+            let path = commands::workflow::run_init(&project)?;
+            say!("🧭 Workflow written to {}", path.display());
+        }
+
+        Commands::Workflow {
+            project,
+            init: false,
+        } => {
+            let workflow = commands::workflow::run(&project)?;
+
+            if output != OutputFormat::Table {
+                #[derive(Serialize)]
+                struct StatusInfo<'a> {
+                    name: &'a str,
+                    kind: StatusKind,
+                    /// `None` when any transition is allowed.
+                    transitions: Option<&'a Vec<String>>,
+                    #[serde(skip_serializing_if = "Option::is_none")]
+                    color: Option<&'a str>,
+                }
+
+                let statuses: Vec<StatusInfo> = workflow
+                    .statuses
+                    .iter()
+                    .map(|s| StatusInfo {
+                        name: s.name.name(),
+                        kind: workflow.kind(&s.name),
+                        transitions: workflow.transitions_from(&s.name),
+                        color: s.color.as_deref(),
+                    })
+                    .collect();
+                return print_records(&statuses, output, |s| {
+                    vec![
+                        s.name.to_string(),
+                        format!("{:?}", s.kind).to_lowercase(),
+                        s.transitions.map_or("any".into(), |t| t.join(",")),
+                    ]
+                });
+            }
+
+            for s in &workflow.statuses {
+                let targets = match workflow.transitions_from(&s.name) {
+                    Some(targets) => targets.join(", "),
+                    None => "any".into(),
+                };
+                println!(
+                    "{:<12} {:<10} → {}",
+                    s.name.name(),
+                    format!("{:?}", workflow.kind(&s.name)).to_lowercase(),
+                    targets
+                );
+            }
+        }
+
+        Commands::Tag { project, id, tags } => {
+            let task = commands::tag::run(&project, id, &tags)?;
+            if output != OutputFormat::Table {
+                return print_tasks(&[task], output);
+            }
+            say!("🏷️  Task {} tags: {}", id, task.tags.join(", "));
+        }
+
+        Commands::Untag { project, id, tags } => {
+            let task = commands::tag::run_untag(&project, id, &tags)?;
+            if output != OutputFormat::Table {
+                return print_tasks(&[task], output);
+            }
+            if task.tags.is_empty() {
+                say!("🏷️  Task {} has no tags", id);
+            } else {
+                say!("🏷️  Task {} tags: {}", id, task.tags.join(", "));
+            }
+        }
+
+        Commands::Tags { project } => {
+            let counts = commands::tags::run(&project)?;
+
+            if output != OutputFormat::Table {
+                #[derive(Serialize)]
+                struct TagCount<'a> {
+                    tag: &'a str,
+                    count: usize,
+                }
+
+                let counts: Vec<TagCount> = counts
+                    .iter()
+                    .map(|(tag, count)| TagCount { tag, count: *count })
+                    .collect();
+                return print_records(&counts, output, |c| {
+                    vec![c.tag.to_string(), c.count.to_string()]
+                });
+            }
+
+            if counts.is_empty() {
+                say!("📭 No tags yet");
+            }
+
+            for (tag, count) in counts {
+                println!("{:<20} {}", tag, count);
+            }
+        }
+
+        Commands::Link {
+            project,
+            id,
+            depends_on,
+        } => {
+            let task = commands::link::run(&project, id, depends_on)?;
+            if output != OutputFormat::Table {
+                return print_tasks(&[task], output);
+            }
+            say!("🔗 Task {} now depends on task {}", id, depends_on);
+        }
+
+        Commands::Unlink {
+            project,
+            id,
+            depends_on,
+        } => {
+            let task = commands::link::run_unlink(&project, id, depends_on)?;
+            if output != OutputFormat::Table {
+                return print_tasks(&[task], output);
+            }
+            say!("✂️  Task {} no longer depends on task {}", id, depends_on);
+        }
+
+        Commands::Graph { project } => {
+            print!("{}", commands::graph::run(&project)?);
+        }
+
+        Commands::List => {
+            let projects = commands::list::run()?;
+
+            if output != OutputFormat::Table {
+                return print_records(&projects, output, |p| {
+                    vec![p.name.clone(), p.created_at.to_rfc3339()]
+                });
+            }
+
+            if projects.is_empty() {
+                println!("No projects found");
+            }
+
+            for meta in projects {
+                say!("📁 {}  (created {})", meta.name, meta.created_at);
+            }
+        }
+
+        Commands::Note { project, note } => {
+            commands::note::run(&project, &note)?;
+            say!("📝 Note added");
+        }
+
+        Commands::Repair { project } => {
+            for report in commands::repair::run(project.as_deref())? {
+                if report.renumbered.is_empty() {
+                    say!("✅ {}: task IDs are unique", report.project);
+                    continue;
+                }
+
+                say!(
+                    "🔧 {}: renumbered {} task(s)",
+                    report.project,
+                    report.renumbered.len()
+                );
+                for (old, new) in report.renumbered {
+                    println!("   {} → {}", old, new);
+                }
+            }
+        }
+
+        Commands::Export {
+            project,
+            query,
+            format,
+            columns,
+            delimiter,
+            file,
+            include_archived,
+        } => {
+            let options = ExportOptions {
+                format,
+                query: join_query(query),
+                include_archived,
+                columns,
+                delimiter,
+            };
+            let exported = commands::export::run(&project, &options)?;
 
-        Commands::List => commands::list::run(),
+            match file {
+                Some(path) => {
+                    storage::write_atomic(&path, &exported)?;
+                    say!("📤 Exported to {}", path.display());
+                }
+                None => print!("{}", exported),
+            }
+        }
 
-        Commands::Note { project, note } => commands::note::run(&project, &note),
+        Commands::Import {
+            file,
+            from,
+            name,
+            force,
+            merge,
+        } => {
+            let options = ImportOptions {
+                from,
+                name,
+                force,
+                merge,
+            };
 
-        Commands::Export { project } => commands::export::run(&project),
+            for imported in commands::import::run(&file, &options)? {
+                if imported.merged {
+                    say!(
+                        "📥 Merged into '{}': {} task(s) added, {} updated",
+                        imported.project,
+                        imported.added,
+                        imported.updated
+                    );
+                } else {
+                    say!(
+                        "📥 Imported {} tasks into '{}'",
+                        imported.added,
+                        imported.project
+                    );
+                }
+            }
+        }
 
-        Commands::Tui { project } => commands::tui::run(&project),
+        Commands::ImportCsv {
+            project,
+            file,
+            map,
+            delimiter,
+            strict,
+        } => {
+            let options = CsvImportOptions {
+                map,
+                delimiter,
+                strict,
+            };
+            let result = commands::import_csv::run(&project, &file, &options)?;
+
+            for error in &result.errors {
+                eprintln!("{}", without_emoji(&format!("⚠️  {}", error)));
+            }
+            say!(
+                "📥 Imported {} task(s), skipped {} row(s)",
+                result.tasks.len(),
+                result.errors.len()
+            );
+        }
+
+        Commands::Tui {
+            project,
+            include_archived,
+        } => commands::tui::run(&project, include_archived)?,
+
+        Commands::DeleteTask { project, id } => {
+            let task = commands::delete_task::run(&project, id)?;
+            if output != OutputFormat::Table {
+                return print_tasks(&[task], output);
+            }
+            say!("🗑️  Task {} deleted", id);
+        }
+
+        Commands::ArchiveTask {
+            project,
+            ids,
+            done_older_than,
+        } => {
+            let archived = match done_older_than {
+                Some(days) => commands::archive_task::run_done_older_than(&project, days)?,
+                None => commands::archive_task::run(&project, &ids)?,
+            };
+            if output != OutputFormat::Table {
+                return print_tasks(&archived, output);
+            }
+            say!("📦 Archived {} task(s)", archived.len());
+        }
+
+        Commands::RestoreTask { project, id } => {
+            let task = commands::restore_task::run(&project, id)?;
+            if output != OutputFormat::Table {
+                return print_tasks(&[task], output);
+            }
+            say!("♻️  Task {} restored", id);
+        }
+
+        Commands::Bulk {
+            project,
+            query,
+            set_status,
+            set_owner,
+            add_tags,
+            archive,
+            force,
+            dry_run,
+        } => {
+            let action = BulkAction {
+                set_status,
+                set_owner,
+                add_tags,
+                archive,
+                force,
+            };
+            let tasks = commands::bulk::run(&project, &query.join(" "), &action, dry_run)?;
+            if output != OutputFormat::Table {
+                return print_tasks(&tasks, output);
+            }
+            let ids: Vec<String> = tasks.iter().map(|t| t.id.to_string()).collect();
+
+            if tasks.is_empty() {
+                say!("🔎 No tasks match the query");
+            } else if dry_run {
+                say!(
+                    "🔎 Would change {} task(s): {}",
+                    tasks.len(),
+                    ids.join(", ")
+                );
+                for task in &tasks {
+                    println!(
+                        "  [{}] {} | {}",
+                        task.id,
+                        task.status.label(),
+                        task.description
+                    );
+                }
+            } else {
+                say!("📦 Changed {} task(s): {}", tasks.len(), ids.join(", "));
+            }
+        }
+
+        Commands::GitInit { project } => {
+            commands::git::init(&project)?;
+            say!("📁 Git repository initialized");
+        }
+
+        Commands::GitCommit { project, message } => {
+            commands::git::commit(&project, &message)?;
+            say!("✅ Commit created");
+        }
+
+        Commands::GitPush { project } => match commands::git::push(&project)? {
+            SyncAuth::Credential => say!("⬆️  Push successful (git credential)"),
+            SyncAuth::Token => say!("⬆️  Push successful (token fallback)"),
+        },
+
+        Commands::GitPull { project } => match commands::git::pull(&project)? {
+            SyncAuth::Credential => say!("⬇️  Pull successful (git credential)"),
+            SyncAuth::Token => say!("⬇️  Pull successful (token fallback)"),
+        },
+    }
+
+    Ok(())
+}
+
+/// Name of `command` when it prints no records `--output` could format.
+fn without_records(command: &Commands) -> Option<&'static str> {
+    match command {
+        Commands::Create { .. } => Some("create"),
+        Commands::Undo { .. } => Some("undo"),
+        Commands::Redo { .. } => Some("redo"),
+        Commands::Workflow { init: true, .. } => Some("workflow --init"),
+        Commands::Graph { .. } => Some("graph"),
+        Commands::Note { .. } => Some("note"),
+        Commands::Repair { .. } => Some("repair"),
+        Commands::Export { .. } => Some("export"),
+        Commands::Import { .. } => Some("import"),
+        Commands::ImportCsv { .. } => Some("import-csv"),
+        Commands::Tui { .. } => Some("tui"),
+        Commands::GitInit { .. } => Some("git-init"),
+        Commands::GitCommit { .. } => Some("git-commit"),
+        Commands::GitPush { .. } => Some("git-push"),
+        Commands::GitPull { .. } => Some("git-pull"),
+        Commands::AddTask { .. }
+        | Commands::EditTask { .. }
+        | Commands::Tasks { .. }
+        | Commands::DoneTask { .. }
+        | Commands::SetStatus { .. }
+        | Commands::ReopenTask { .. }
+        | Commands::TaskLog { .. }
+        | Commands::Workflow { init: false, .. }
+        | Commands::Tag { .. }
+        | Commands::Untag { .. }
+        | Commands::Tags { .. }
+        | Commands::Link { .. }
+        | Commands::Unlink { .. }
+        | Commands::List
+        | Commands::DeleteTask { .. }
+        | Commands::ArchiveTask { .. }
+        | Commands::RestoreTask { .. }
+        | Commands::Bulk { .. } => None,
+    }
+}
+
+/// Query words from the command line, so `tasks web tag:api is:open` needs
+/// no quoting.
+fn join_query(words: Vec<String>) -> Option<String> {
+    if words.is_empty() {
+        return None;
+    }
+    Some(words.join(" "))
+}
+
+/// `--delimiter` value: a single character, or `tab`.
+fn parse_delimiter(s: &str) -> std::result::Result<char, String> {
+    match s {
+        "tab" | "\\t" => Ok('\t'),
+        _ => {
+            let mut chars = s.chars();
+            match (chars.next(), chars.next()) {
+                (Some(c), None) => Ok(c),
+                _ => Err(format!("expected a single character or 'tab', got '{}'", s)),
+            }
+        }
+    }
+}
+
+fn parse_mapping(s: &str) -> std::result::Result<(Column, String), String> {
+    match s.split_once('=') {
+        Some((field, header)) if !header.trim().is_empty() => {
+            Ok((field.parse()?, header.trim().to_string()))
+        }
+        _ => Err(format!("expected FIELD=HEADER, got '{}'", s)),
+    }
+}
+
+/// `message` without its leading emoji when `--no-emoji` is set.
+fn without_emoji(message: &str) -> &str {
+    if !NO_EMOJI.load(Ordering::Relaxed) || message.starts_with(|c: char| c.is_ascii()) {
+        return message;
+    }
+    message
+        .trim_start_matches(|c: char| !c.is_whitespace())
+        .trim_start()
+}
+
+/// Print `items` as JSON, or as tab-separated `fields` in plain format.
+fn print_records<T: Serialize>(
+    items: &[T],
+    output: OutputFormat,
+    fields: impl Fn(&T) -> Vec<String>,
+) -> Result<()> {
+    if output != OutputFormat::Plain {
+        return print_json(items, output);
+    }
+
+    let mut out = io::stdout().lock();
+    for item in items {
+        writeln!(out, "{}", plain_line(&fields(item)))?;
+    }
+    Ok(())
+}
+
+/// One plain-format record. Backslashes, tabs and line breaks inside a
+/// field are escaped as `\\`, `\t`, `\n` and `\r`, so every record stays
+/// on one line with one tab between fields.
+fn plain_line(fields: &[String]) -> String {
+    fields
+        .iter()
+        .map(|field| {
+            field
+                .replace('\\', "\\\\")
+                .replace('\t', "\\t")
+                .replace('\n', "\\n")
+                .replace('\r', "\\r")
+        })
+        .collect::<Vec<_>>()
+        .join("\t")
+}
+
+/// Print `tasks` as JSON, or one tab-separated line per task.
+fn print_tasks(tasks: &[Task], output: OutputFormat) -> Result<()> {
+    print_records(tasks, output, |t| {
+        vec![
+            t.id.to_string(),
+            t.status.to_string(),
+            t.priority.to_string(),
+            t.deadline.map(|d| d.to_string()).unwrap_or_default(),
+            t.owner.clone(),
+            t.tags.join(","),
+            t.description.clone(),
+        ]
+    })
+}
+
+/// Print `items` as one JSON array, or one object per line for ndjson.
+fn print_json<T: Serialize>(items: &[T], output: OutputFormat) -> Result<()> {
+    let mut out = io::stdout().lock();
+
+    if output == OutputFormat::Ndjson {
+        for item in items {
+            writeln!(
+                out,
+                "{}",
+                serde_json::to_string(item).map_err(io::Error::from)?
+            )?;
+        }
+        return Ok(());
+    }
+
+    writeln!(
+        out,
+        "{}",
+        serde_json::to_string_pretty(items).map_err(io::Error::from)?
+    )?;
+    Ok(())
+}
+
+fn print_row(row: &TreeRow) {
+    let task = &row.task;
+
+    let archived = if task.archived_at.is_some() {
+        " | archived"
+    } else {
+        ""
+    };
+
+    let indent = match row.depth {
+        0 => String::new(),
+        depth => format!("{}└─ ", "   ".repeat(depth - 1)),
+    };
+
+    let progress = row
+        .progress
+        .map(|p| format!(" ({}% of {})", p.percent(), p.total))
+        .unwrap_or_default();
+
+    let (status, waiting) = if row.waiting_on.is_empty() {
+        let status = format!("{:<11}", task.status.label());
+        let status = match row.kind {
+            StatusKind::Todo => status.normal(),
+            StatusKind::Active => status.yellow(),
+            StatusKind::Blocked => status.red(),
+            StatusKind::Done => status.green(),
+            StatusKind::Cancelled => status.dimmed(),
+        };
+        (status, String::new())
+    } else {
+        let ids: Vec<String> = row.waiting_on.iter().map(|id| id.to_string()).collect();
+        (
+            format!("{:<11}", "BLOCKED").red(),
+            format!(" | waiting on {}", ids.join(", ")),
+        )
+    };
+
+    let priority = format!("{:<6}", task.priority);
+    let priority = match task.priority.level() {
+        9.. => priority.red(),
+        7..=8 => priority.yellow(),
+        _ => priority.normal(),
+    };
+
+    let tags: String = task.tags.iter().map(|t| format!(" #{}", t)).collect();
+
+    println!(
+        "[{}] {} | {} | {}{}{}{} | owner: {}{}{}",
+        task.id,
+        status,
+        priority,
+        indent,
+        task.description,
+        progress,
+        tags,
+        task.owner,
+        waiting,
+        archived
+    );
+}
 
-        Commands::GitInit { project } => commands::git::init(&project),
+#[cfg(test)]
+mod tests {
+    use super::*;
 
-        Commands::GitCommit { project, message } => commands::git::commit(&project, &message),
+    #[test]
+    fn plain_records_stay_on_one_line() {
+        let fields = [
+            "7".to_string(),
+            "Write, \"quote\" and\nbreak\tnow".to_string(),
+            "C:\\tmp\r".to_string(),
+        ];
 
-        Commands::GitPush { project } => commands::git::push(&project),
+        let line = plain_line(&fields);
 
-        Commands::GitPull { project } => commands::git::pull(&project),
+        assert_eq!(line, "7\tWrite, \"quote\" and\\nbreak\\tnow\tC:\\\\tmp\\r");
+        assert_eq!(line.split('\t').count(), 3);
+        assert!(!line.contains('\n'));
     }
 }
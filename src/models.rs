@@ -1,31 +1,418 @@
 use chrono::{DateTime, Local, NaiveDate};
 use serde::{Deserialize, Serialize};
+use std::collections::BTreeMap;
+use std::fmt;
+use std::str::FromStr;
 
-#[derive(Serialize, Deserialize, Debug, Clone)]
+/// Task status.
+///
+/// The five built-in statuses always exist; projects with a custom workflow
+/// (`workflow.toml`) add their own as `Custom`. Serialized as its name, e.g.
+/// `"InProgress"` or `"Review"`.
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
 pub enum Status {
     Todo,
+    InProgress,
     Done,
     Blocked,
+    Cancelled,
+    Custom(String),
 }
 
-#[derive(Serialize, Deserialize, Debug, Clone)]
+/// What a status means for progress, whatever it is called.
+#[derive(Serialize, Deserialize, Debug, Clone, Copy, PartialEq, Eq, Default)]
+#[serde(rename_all = "kebab-case")]
+pub enum StatusKind {
+    #[default]
+    Todo,
+    Active,
+    Blocked,
+    Done,
+    Cancelled,
+}
+
+impl StatusKind {
+    /// Done or cancelled: no more work expected.
+    pub fn is_closed(self) -> bool {
+        matches!(self, StatusKind::Done | StatusKind::Cancelled)
+    }
+}
+
+impl Status {
+    pub const BUILT_IN: [Status; 5] = [
+        Status::Todo,
+        Status::InProgress,
+        Status::Blocked,
+        Status::Done,
+        Status::Cancelled,
+    ];
+
+    /// Name as stored on disk.
+    pub fn name(&self) -> &str {
+        match self {
+            Status::Todo => "Todo",
+            Status::InProgress => "InProgress",
+            Status::Done => "Done",
+            Status::Blocked => "Blocked",
+            Status::Cancelled => "Cancelled",
+            Status::Custom(name) => name,
+        }
+    }
+
+    /// Inverse of [`name`](Self::name), matched like [`status_key`] so that
+    /// `todo` or `in-progress` in a workflow is the built-in status rather
+    /// than a custom one shadowing it; other names become `Custom`.
+    pub fn from_name(name: &str) -> Status {
+        match status_key(name).as_str() {
+            "todo" => Status::Todo,
+            "inprogress" => Status::InProgress,
+            "done" => Status::Done,
+            "blocked" => Status::Blocked,
+            "cancelled" | "canceled" => Status::Cancelled,
+            _ => Status::Custom(name.to_string()),
+        }
+    }
+
+    /// Kind of a built-in status; custom ones get theirs from the workflow.
+    pub fn builtin_kind(&self) -> Option<StatusKind> {
+        match self {
+            Status::Todo => Some(StatusKind::Todo),
+            Status::InProgress => Some(StatusKind::Active),
+            Status::Done => Some(StatusKind::Done),
+            Status::Blocked => Some(StatusKind::Blocked),
+            Status::Cancelled => Some(StatusKind::Cancelled),
+            Status::Custom(_) => None,
+        }
+    }
+
+    /// Upper-case label used in listings.
+    pub fn label(&self) -> String {
+        match self {
+            Status::InProgress => "IN PROGRESS".into(),
+            _ => self.name().to_uppercase(),
+        }
+    }
+}
+
+impl Serialize for Status {
+    fn serialize<S: serde::Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        serializer.serialize_str(self.name())
+    }
+}
+
+impl<'de> Deserialize<'de> for Status {
+    fn deserialize<D: serde::Deserializer<'de>>(deserializer: D) -> Result<Self, D::Error> {
+        let name = String::deserialize(deserializer)?;
+        Ok(Status::from_name(&name))
+    }
+}
+
+impl fmt::Display for Status {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let name = match self {
+            Status::Todo => "todo",
+            Status::InProgress => "in-progress",
+            Status::Done => "done",
+            Status::Blocked => "blocked",
+            Status::Cancelled => "cancelled",
+            Status::Custom(name) => name,
+        };
+        f.write_str(name)
+    }
+}
+
+impl FromStr for Status {
+    type Err = String;
+
+    /// Parses built-in statuses only. Case-insensitive; `-`, `_` and spaces
+    /// are ignored (`in-progress`, `InProgress`, `in progress`). Custom
+    /// statuses are resolved through the project's workflow.
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match status_key(s).as_str() {
+            "todo" => Ok(Status::Todo),
+            "inprogress" | "doing" | "started" => Ok(Status::InProgress),
+            "done" => Ok(Status::Done),
+            "blocked" => Ok(Status::Blocked),
+            "cancelled" | "canceled" => Ok(Status::Cancelled),
+            _ => Err(format!(
+                "unknown status '{}' (expected todo, in-progress, blocked, done or cancelled)",
+                s
+            )),
+        }
+    }
+}
+
+/// Normalised form of a status name for lenient matching.
+pub(crate) fn status_key(s: &str) -> String {
+    s.chars()
+        .filter(|c| !matches!(c, '-' | '_' | ' '))
+        .collect::<String>()
+        .to_lowercase()
+}
+
+/// Task priority: one of four named levels or a number from 0 (lowest) to
+/// 9 (highest). The names stand for 3, 5, 7 and 9, so `7` and `high` are
+/// the same priority: comparison, equality and hashing all go by
+/// [`level`](Priority::level).
+#[derive(Debug, Clone, Copy, Default)]
+pub enum Priority {
+    Low,
+    #[default]
+    Medium,
+    High,
+    Urgent,
+    /// A level without a name; see [`Priority::from_level`]. Levels above
+    /// 9 count as 9.
+    Level(u8),
+}
+
+impl Priority {
+    pub const MAX_LEVEL: u8 = 9;
+
+    /// Priority for a numeric level, using the name where one exists.
+    /// Levels above 9 are capped.
+    pub fn from_level(level: u8) -> Priority {
+        match level.min(Self::MAX_LEVEL) {
+            3 => Priority::Low,
+            5 => Priority::Medium,
+            7 => Priority::High,
+            9 => Priority::Urgent,
+            level => Priority::Level(level),
+        }
+    }
+
+    pub fn level(self) -> u8 {
+        match self {
+            Priority::Low => 3,
+            Priority::Medium => 5,
+            Priority::High => 7,
+            Priority::Urgent => 9,
+            Priority::Level(level) => level.min(Self::MAX_LEVEL),
+        }
+    }
+}
+
+impl PartialEq for Priority {
+    fn eq(&self, other: &Self) -> bool {
+        self.level() == other.level()
+    }
+}
+
+impl Eq for Priority {}
+
+impl std::hash::Hash for Priority {
+    fn hash<H: std::hash::Hasher>(&self, state: &mut H) {
+        self.level().hash(state);
+    }
+}
+
+impl Ord for Priority {
+    fn cmp(&self, other: &Self) -> std::cmp::Ordering {
+        self.level().cmp(&other.level())
+    }
+}
+
+impl PartialOrd for Priority {
+    fn partial_cmp(&self, other: &Self) -> Option<std::cmp::Ordering> {
+        Some(self.cmp(other))
+    }
+}
+
+impl fmt::Display for Priority {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        // `pad` so listings can align the column with `{:<6}`; levels
+        // with a name are shown by it
+        match Priority::from_level(self.level()) {
+            Priority::Low => f.pad("low"),
+            Priority::Medium => f.pad("medium"),
+            Priority::High => f.pad("high"),
+            Priority::Urgent => f.pad("urgent"),
+            Priority::Level(level) => f.pad(&level.to_string()),
+        }
+    }
+}
+
+impl FromStr for Priority {
+    type Err = String;
+
+    /// Case-insensitive name (`med` and `normal` are accepted for medium) or
+    /// a level from 0 to 9.
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s.trim().to_lowercase().as_str() {
+            "low" => Ok(Priority::Low),
+            "medium" | "med" | "normal" => Ok(Priority::Medium),
+            "high" => Ok(Priority::High),
+            "urgent" => Ok(Priority::Urgent),
+            other => match other.parse::<u8>() {
+                Ok(level) if level <= Self::MAX_LEVEL => Ok(Priority::from_level(level)),
+                _ => Err(format!(
+                    "unknown priority '{}' (expected low, medium, high, urgent or 0-9)",
+                    s
+                )),
+            },
+        }
+    }
+}
+
+impl Serialize for Priority {
+    fn serialize<S: serde::Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        serializer.collect_str(self)
+    }
+}
+
+impl<'de> Deserialize<'de> for Priority {
+    /// Accepts bare numbers as levels, so files written before priorities
+    /// were checked still load; an unknown name is an error naming it.
+    fn deserialize<D: serde::Deserializer<'de>>(deserializer: D) -> Result<Self, D::Error> {
+        #[derive(Deserialize)]
+        #[serde(untagged)]
+        enum Raw {
+            Level(u64),
+            Name(String),
+        }
+
+        Ok(match Raw::deserialize(deserializer)? {
+            Raw::Level(level) => Priority::from_level(level.min(u8::MAX as u64) as u8),
+            Raw::Name(name) => name.parse().map_err(serde::de::Error::custom)?,
+        })
+    }
+}
+
+/// Names only, unlike `Priority`'s `Deserialize`, which also takes bare
+/// numbers from old task files.
+fn strict_priority<'de, D: serde::Deserializer<'de>>(
+    deserializer: D,
+) -> Result<Option<Priority>, D::Error> {
+    Option::<String>::deserialize(deserializer)?
+        .map(|name| name.parse().map_err(serde::de::Error::custom))
+        .transpose()
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
 pub struct Task {
     pub id: u32,
     pub description: String,
     pub status: Status,
-    pub priority: String,
+    pub priority: Priority,
     pub deadline: Option<NaiveDate>,
     pub owner: String,
+    /// Task this one is a subtask of.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub parent: Option<u32>,
+    /// Tasks that must be done before this one can start.
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub depends_on: Vec<u32>,
+    /// Lower-case labels such as `backend` or `bug`.
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub tags: Vec<String>,
+    /// Unknown for tasks created before timestamps were recorded.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub created_at: Option<DateTime<Local>>,
+    /// Last time any field changed.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub updated_at: Option<DateTime<Local>>,
+    /// When the task was last marked done.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub completed_at: Option<DateTime<Local>>,
+    /// When the task was moved to `archive.json`.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub archived_at: Option<DateTime<Local>>,
+    /// UUID of a task imported from Taskwarrior, written back on export so
+    /// Taskwarrior updates the task instead of adding a copy.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub uuid: Option<String>,
 }
 
 #[derive(Serialize, Deserialize, Debug, Clone)]
 pub struct Project {
     pub name: String,
     pub created_at: DateTime<Local>,
+    /// Next task ID to hand out. Never decreases, so IDs are not reused.
+    #[serde(default)]
+    pub next_task_id: u32,
 }
 
 #[derive(Serialize, Deserialize, Debug, Clone)]
 pub struct Config {
-    pub default_priority: Option<String>,
+    #[serde(default, deserialize_with = "strict_priority")]
+    pub default_priority: Option<Priority>,
     pub date_format: Option<String>,
+    /// TUI colour per tag, e.g. `backend = "cyan"`.
+    #[serde(default)]
+    pub tag_colors: BTreeMap<String, String>,
+    /// Saved filter expressions, used as `@name` in queries.
+    #[serde(default)]
+    pub queries: BTreeMap<String, String>,
+}
+
+#[cfg(test)]
+mod tests {
+    use std::cmp::Ordering;
+    use std::collections::HashSet;
+
+    use super::*;
+
+    fn priority(json: &str) -> Priority {
+        serde_json::from_str(json).unwrap()
+    }
+
+    #[test]
+    fn parses_names_and_levels() {
+        assert_eq!("HIGH".parse(), Ok(Priority::High));
+        assert_eq!(" normal ".parse(), Ok(Priority::Medium));
+        assert_eq!("7".parse(), Ok(Priority::High));
+        assert_eq!("8".parse(), Ok(Priority::Level(8)));
+        assert!("10".parse::<Priority>().is_err());
+        assert!("highest".parse::<Priority>().is_err());
+    }
+
+    #[test]
+    fn orders_by_level() {
+        assert!(Priority::Level(8) > Priority::High);
+        assert!(Priority::Low > Priority::Level(0));
+        assert_eq!(Priority::from_level(200), Priority::Urgent);
+    }
+
+    #[test]
+    fn equal_levels_are_the_same_priority() {
+        assert_eq!(Priority::Level(7), Priority::High);
+        assert_eq!(Priority::Level(7).cmp(&Priority::High), Ordering::Equal);
+        assert_eq!(Priority::Level(200), Priority::Urgent);
+        assert_ne!(Priority::Level(8), Priority::Urgent);
+        assert_eq!(Priority::Level(7).to_string(), "high");
+        assert_eq!(Priority::Level(200).to_string(), "urgent");
+
+        let set: HashSet<Priority> = [Priority::Level(3), Priority::Low, Priority::Level(4)].into();
+        assert_eq!(set.len(), 2);
+    }
+
+    #[test]
+    fn deserializes_old_files_but_not_unknown_names() {
+        assert_eq!(priority(r#""urgent""#), Priority::Urgent);
+        assert_eq!(priority(r#""Med""#), Priority::Medium);
+        assert_eq!(priority("4"), Priority::Level(4));
+        assert_eq!(priority("5"), Priority::Medium);
+        assert_eq!(priority("1000"), Priority::Urgent);
+        assert_eq!(
+            serde_json::to_string(&[Priority::Level(4), Priority::High]).unwrap(),
+            r#"["4","high"]"#
+        );
+        assert_eq!(priority(r#""4""#), Priority::Level(4));
+
+        let err = serde_json::from_str::<Priority>(r#""whenever""#).unwrap_err();
+        assert!(
+            err.to_string().contains("unknown priority 'whenever'"),
+            "{}",
+            err
+        );
+        assert!(serde_json::from_str::<Priority>(r#""12""#).is_err());
+    }
+
+    #[test]
+    fn config_priority_is_strict() {
+        let config: Config = toml::from_str("default_priority = \"high\"").unwrap();
+        assert_eq!(config.default_priority, Some(Priority::High));
+        assert_eq!(toml::from_str::<Config>("").unwrap().default_priority, None);
+        assert!(toml::from_str::<Config>("default_priority = \"hgih\"").is_err());
+    }
 }
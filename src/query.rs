@@ -0,0 +1,722 @@
+//! Task filter expressions.
+//!
+//! ```text
+//! status:todo and (priority>=high or due<+3d) and owner:me and tag:api
+//! ```
+//!
+//! Terms are `field:value` (or `field<value`, `<=`, `>`, `>=` where an order
+//! makes sense) and bare words, which search the description. Terms next to
+//! each other must all match; `and`, `or`, `not` (or a leading `!`) and
+//! parentheses combine them as usual. `@name` stands for a saved query from
+//! the config.
+//!
+//! | field      | values                                                  |
+//! |------------|---------------------------------------------------------|
+//! | `status`   | a status of the project's workflow                      |
+//! | `is`       | `open`, `closed`, `ready`, `blocked`, `overdue`, `archived` |
+//! | `priority` | `low` … `urgent` or 0-9, comparable                     |
+//! | `owner`    | a user name, `me` for the current user                  |
+//! | `tag`      | a tag                                                   |
+//! | `due`      | a date, comparable; `none` for tasks without deadline   |
+//! | `created`  | a date, comparable                                      |
+//! | `text`     | text in the description (quote it to include spaces)    |
+//! | `id`, `parent` | a task ID                                           |
+//!
+//! Dates are written in the configured `date_format` or as `YYYY-MM-DD`,
+//! or are `today`, `tomorrow`, `yesterday` or an offset from today such as
+//! `+3d`, `-2w` or `10d`.
+
+use std::fmt;
+
+use chrono::{Local, NaiveDate, TimeDelta};
+
+use crate::config;
+use crate::deps;
+use crate::error::{PmError, Result};
+use crate::models::{Config, Priority, Status, Task};
+use crate::tags;
+use crate::workflow::Workflow;
+
+/// A parsed filter expression.
+#[derive(Debug, Clone, PartialEq, Default)]
+pub struct Query {
+    /// `None` for an empty query, which matches every task.
+    expr: Option<Expr>,
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub enum Expr {
+    And(Box<Expr>, Box<Expr>),
+    Or(Box<Expr>, Box<Expr>),
+    Not(Box<Expr>),
+    Term(Term),
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub enum Term {
+    Status(Status),
+    Is(State),
+    Priority(Cmp, Priority),
+    /// `None` for `me`.
+    Owner(Option<String>),
+    Tag(String),
+    Due(Cmp, DateSpec),
+    NoDue,
+    Created(Cmp, DateSpec),
+    Text(String),
+    Id(u32),
+    Parent(u32),
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum State {
+    Open,
+    Closed,
+    Ready,
+    Blocked,
+    Overdue,
+    Archived,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Cmp {
+    Eq,
+    Lt,
+    Le,
+    Gt,
+    Ge,
+}
+
+/// A date in a query, resolved against today's date when matching.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum DateSpec {
+    On(NaiveDate),
+    DaysFromToday(i64),
+}
+
+/// What matching needs to know beyond the task itself.
+pub struct Context<'a> {
+    pub workflow: &'a Workflow,
+    /// Every task of the project, archived ones included, for looking up
+    /// dependencies.
+    pub tasks: &'a [Task],
+    /// Login name `owner:me` stands for.
+    pub user: &'a str,
+    pub today: NaiveDate,
+}
+
+impl Query {
+    /// Parse `input`. Status names are checked against `workflow`; `@name`
+    /// is looked up in the config's saved queries.
+    pub fn parse(input: &str, workflow: &Workflow, cfg: &Config) -> Result<Self> {
+        let expr = Parser::new(input, workflow, cfg, Vec::new())?.parse_all()?;
+        Ok(Query { expr })
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.expr.is_none()
+    }
+
+    pub fn matches(&self, task: &Task, ctx: &Context) -> bool {
+        self.expr.as_ref().is_none_or(|e| e.matches(task, ctx))
+    }
+}
+
+impl Expr {
+    pub fn matches(&self, task: &Task, ctx: &Context) -> bool {
+        match self {
+            Expr::And(a, b) => a.matches(task, ctx) && b.matches(task, ctx),
+            Expr::Or(a, b) => a.matches(task, ctx) || b.matches(task, ctx),
+            Expr::Not(e) => !e.matches(task, ctx),
+            Expr::Term(term) => term.matches(task, ctx),
+        }
+    }
+}
+
+impl Term {
+    fn matches(&self, task: &Task, ctx: &Context) -> bool {
+        let workflow = ctx.workflow;
+        let blocked = || deps::is_blocked(ctx.tasks, workflow, task);
+
+        match self {
+            Term::Status(status) => deps::has_status(ctx.tasks, workflow, task, status),
+            Term::Is(State::Open) => !workflow.is_closed(&task.status),
+            Term::Is(State::Closed) => workflow.is_closed(&task.status),
+            Term::Is(State::Ready) => !workflow.is_closed(&task.status) && !blocked(),
+            Term::Is(State::Blocked) => blocked(),
+            Term::Is(State::Overdue) => {
+                !workflow.is_closed(&task.status) && task.deadline.is_some_and(|d| d < ctx.today)
+            }
+            Term::Is(State::Archived) => task.archived_at.is_some(),
+            Term::Priority(cmp, priority) => cmp.test(task.priority, *priority),
+            Term::Owner(None) => task.owner.eq_ignore_ascii_case(ctx.user),
+            Term::Owner(Some(owner)) => task.owner.eq_ignore_ascii_case(owner),
+            Term::Tag(tag) => task.tags.contains(tag),
+            Term::Due(cmp, date) => task
+                .deadline
+                .is_some_and(|d| cmp.test(d, date.resolve(ctx.today))),
+            Term::NoDue => task.deadline.is_none(),
+            Term::Created(cmp, date) => task
+                .created_at
+                .is_some_and(|c| cmp.test(c.date_naive(), date.resolve(ctx.today))),
+            Term::Text(text) => task.description.to_lowercase().contains(text),
+            Term::Id(id) => task.id == *id,
+            Term::Parent(id) => task.parent == Some(*id),
+        }
+    }
+}
+
+impl State {
+    fn parse(s: &str) -> Result<State> {
+        match s.to_lowercase().as_str() {
+            "open" => Ok(State::Open),
+            "closed" => Ok(State::Closed),
+            "ready" => Ok(State::Ready),
+            "blocked" => Ok(State::Blocked),
+            "overdue" => Ok(State::Overdue),
+            "archived" => Ok(State::Archived),
+            _ => Err(invalid(&format!(
+                "unknown state 'is:{}' (expected open, closed, ready, blocked, overdue or archived)",
+                s
+            ))),
+        }
+    }
+}
+
+impl Cmp {
+    fn test<T: Ord>(self, value: T, bound: T) -> bool {
+        match self {
+            Cmp::Eq => value == bound,
+            Cmp::Lt => value < bound,
+            Cmp::Le => value <= bound,
+            Cmp::Gt => value > bound,
+            Cmp::Ge => value >= bound,
+        }
+    }
+}
+
+impl DateSpec {
+    /// Offsets are checked against today's date when parsed, so this only
+    /// saturates past the end of the calendar for a query that outlives
+    /// the day it was parsed on.
+    fn resolve(self, today: NaiveDate) -> NaiveDate {
+        match self {
+            DateSpec::On(date) => date,
+            DateSpec::DaysFromToday(days) => offset(today, days).unwrap_or(if days < 0 {
+                NaiveDate::MIN
+            } else {
+                NaiveDate::MAX
+            }),
+        }
+    }
+
+    fn parse(s: &str, cfg: &Config) -> Option<DateSpec> {
+        let s = s.to_lowercase();
+        match s.as_str() {
+            "today" => return Some(DateSpec::DaysFromToday(0)),
+            "tomorrow" => return Some(DateSpec::DaysFromToday(1)),
+            "yesterday" => return Some(DateSpec::DaysFromToday(-1)),
+            _ => {}
+        }
+
+        // ISO dates always work, as in `export` and the JSON output
+        let date = config::parse_date(cfg, &s)
+            .ok()
+            .or_else(|| NaiveDate::parse_from_str(&s, "%Y-%m-%d").ok());
+        if let Some(date) = date {
+            return Some(DateSpec::On(date));
+        }
+
+        // +3d, -2w, 10d
+        let (sign, rest) = match s.strip_prefix('-') {
+            Some(rest) => (-1, rest),
+            None => (1, s.strip_prefix('+').unwrap_or(&s)),
+        };
+        let (count, days_per_unit) = match (rest.strip_suffix('d'), rest.strip_suffix('w')) {
+            (Some(count), _) => (count, 1),
+            (_, Some(count)) => (count, 7),
+            _ => return None,
+        };
+        // Digits only, so `+-3d` is not taken for `-3d`
+        if !count.bytes().all(|b| b.is_ascii_digit()) {
+            return None;
+        }
+        let days = count
+            .parse::<i64>()
+            .ok()?
+            .checked_mul(days_per_unit * sign)?;
+
+        offset(Local::now().date_naive(), days)?;
+        Some(DateSpec::DaysFromToday(days))
+    }
+}
+
+/// `days` after `date`, or `None` past the range of dates.
+fn offset(date: NaiveDate, days: i64) -> Option<NaiveDate> {
+    date.checked_add_signed(TimeDelta::try_days(days)?)
+}
+
+#[derive(Debug, Clone, PartialEq)]
+enum Token {
+    Open,
+    Close,
+    And,
+    Or,
+    Not,
+    Word(String),
+    /// Quoted text, always a description search.
+    Quoted(String),
+}
+
+impl fmt::Display for Token {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            Token::Open => write!(f, "'('"),
+            Token::Close => write!(f, "')'"),
+            Token::And => write!(f, "'and'"),
+            Token::Or => write!(f, "'or'"),
+            Token::Not => write!(f, "'not'"),
+            Token::Word(word) => write!(f, "'{}'", word),
+            Token::Quoted(text) => write!(f, "\"{}\"", text),
+        }
+    }
+}
+
+fn tokenize(input: &str) -> Result<Vec<Token>> {
+    let mut tokens = Vec::new();
+    let mut chars = input.chars().peekable();
+
+    while let Some(&c) = chars.peek() {
+        match c {
+            _ if c.is_whitespace() => {
+                chars.next();
+            }
+            '(' => {
+                chars.next();
+                tokens.push(Token::Open);
+            }
+            ')' => {
+                chars.next();
+                tokens.push(Token::Close);
+            }
+            '!' => {
+                chars.next();
+                tokens.push(Token::Not);
+            }
+            '"' => {
+                chars.next();
+                tokens.push(Token::Quoted(quoted(&mut chars)?));
+            }
+            _ => {
+                let mut word = String::new();
+                while let Some(&c) = chars.peek() {
+                    if c.is_whitespace() || c == '(' || c == ')' {
+                        break;
+                    }
+                    // `text:"two words"`
+                    if c == '"' {
+                        chars.next();
+                        word.push_str(&quoted(&mut chars)?);
+                        continue;
+                    }
+                    word.push(c);
+                    chars.next();
+                }
+
+                tokens.push(match word.to_lowercase().as_str() {
+                    "and" | "&&" => Token::And,
+                    "or" | "||" => Token::Or,
+                    "not" => Token::Not,
+                    _ => Token::Word(word),
+                });
+            }
+        }
+    }
+
+    Ok(tokens)
+}
+
+/// Text up to the closing quote, which is consumed.
+fn quoted(chars: &mut impl Iterator<Item = char>) -> Result<String> {
+    let mut text = String::new();
+    for c in chars {
+        if c == '"' {
+            return Ok(text);
+        }
+        text.push(c);
+    }
+    Err(invalid("missing closing '\"'"))
+}
+
+/// Recursive descent over `or` > `and` > `not` > atoms.
+struct Parser<'a> {
+    tokens: Vec<Token>,
+    pos: usize,
+    workflow: &'a Workflow,
+    cfg: &'a Config,
+    /// Saved queries being expanded, to catch one that uses itself.
+    expanding: Vec<String>,
+}
+
+impl<'a> Parser<'a> {
+    fn new(
+        input: &str,
+        workflow: &'a Workflow,
+        cfg: &'a Config,
+        expanding: Vec<String>,
+    ) -> Result<Self> {
+        Ok(Parser {
+            tokens: tokenize(input)?,
+            pos: 0,
+            workflow,
+            cfg,
+            expanding,
+        })
+    }
+
+    fn parse_all(&mut self) -> Result<Option<Expr>> {
+        if self.tokens.is_empty() {
+            return Ok(None);
+        }
+
+        let expr = self.or()?;
+        match self.peek() {
+            None => Ok(Some(expr)),
+            Some(Token::Close) => Err(invalid("unmatched ')'")),
+            Some(token) => Err(invalid(&format!("unexpected {}", token))),
+        }
+    }
+
+    fn peek(&self) -> Option<&Token> {
+        self.tokens.get(self.pos)
+    }
+
+    fn next(&mut self) -> Option<Token> {
+        let token = self.tokens.get(self.pos).cloned();
+        self.pos += 1;
+        token
+    }
+
+    fn or(&mut self) -> Result<Expr> {
+        let mut expr = self.and()?;
+
+        while self.peek() == Some(&Token::Or) {
+            self.next();
+            expr = Expr::Or(Box::new(expr), Box::new(self.and()?));
+        }
+        Ok(expr)
+    }
+
+    /// `a and b`, or just `a b`.
+    fn and(&mut self) -> Result<Expr> {
+        let mut expr = self.not()?;
+
+        loop {
+            match self.peek() {
+                Some(Token::And) => {
+                    self.next();
+                }
+                Some(Token::Open | Token::Not | Token::Word(_) | Token::Quoted(_)) => {}
+                _ => return Ok(expr),
+            }
+            expr = Expr::And(Box::new(expr), Box::new(self.not()?));
+        }
+    }
+
+    fn not(&mut self) -> Result<Expr> {
+        if self.peek() == Some(&Token::Not) {
+            self.next();
+            return Ok(Expr::Not(Box::new(self.not()?)));
+        }
+        self.atom()
+    }
+
+    fn atom(&mut self) -> Result<Expr> {
+        match self.next() {
+            Some(Token::Open) => {
+                let expr = self.or()?;
+                match self.next() {
+                    Some(Token::Close) => Ok(expr),
+                    _ => Err(invalid("missing ')'")),
+                }
+            }
+            Some(Token::Quoted(text)) => Ok(Expr::Term(Term::Text(text.to_lowercase()))),
+            Some(Token::Word(word)) => match word.strip_prefix('@') {
+                Some(name) => self.saved_query(name),
+                None => Ok(Expr::Term(self.term(&word)?)),
+            },
+            Some(token) => Err(invalid(&format!("unexpected {}", token))),
+            None => Err(invalid("unexpected end of query")),
+        }
+    }
+
+    fn saved_query(&mut self, name: &str) -> Result<Expr> {
+        let text = self
+            .cfg
+            .queries
+            .get(name)
+            .ok_or_else(|| invalid(&format!("no saved query '@{}' in the config", name)))?;
+
+        if self.expanding.iter().any(|n| n == name) {
+            return Err(invalid(&format!(
+                "saved query '@{}' refers to itself",
+                name
+            )));
+        }
+
+        let mut expanding = self.expanding.clone();
+        expanding.push(name.to_string());
+
+        Parser::new(text, self.workflow, self.cfg, expanding)?
+            .parse_all()?
+            .ok_or_else(|| invalid(&format!("saved query '@{}' is empty", name)))
+    }
+
+    fn term(&self, word: &str) -> Result<Term> {
+        let Some(at) = word.find([':', '<', '>', '=']) else {
+            return Ok(Term::Text(word.to_lowercase()));
+        };
+
+        let field = word[..at].to_lowercase();
+        let rest = &word[at..];
+        let (cmp, value) = [
+            ("<=", Cmp::Le),
+            (">=", Cmp::Ge),
+            ("<", Cmp::Lt),
+            (">", Cmp::Gt),
+            (":", Cmp::Eq),
+            ("=", Cmp::Eq),
+        ]
+        .iter()
+        .find_map(|(op, cmp)| rest.strip_prefix(op).map(|v| (*cmp, v)))
+        .unwrap_or((Cmp::Eq, rest));
+
+        if value.is_empty() {
+            return Err(invalid(&format!("'{}' needs a value", field)));
+        }
+
+        let ordered = |term: Term| match (cmp, &term) {
+            (Cmp::Eq, _) | (_, Term::Priority(..) | Term::Due(..) | Term::Created(..)) => Ok(term),
+            _ => Err(invalid(&format!(
+                "'{}' cannot be compared with < or >",
+                field
+            ))),
+        };
+        let date = || {
+            DateSpec::parse(value, self.cfg).ok_or_else(|| {
+                invalid(&format!(
+                    "invalid date '{}' (expected {}, today or an offset like +3d)",
+                    value,
+                    self.cfg.date_format.as_deref().unwrap_or("YYYY-MM-DD")
+                ))
+            })
+        };
+        let id = || {
+            value
+                .parse::<u32>()
+                .map_err(|_| invalid(&format!("invalid task id '{}'", value)))
+        };
+
+        ordered(match field.as_str() {
+            "status" => Term::Status(self.workflow.resolve(value)?),
+            "is" => Term::Is(State::parse(value)?),
+            "priority" | "prio" => {
+                Term::Priority(cmp, value.parse().map_err(|e: String| invalid(&e))?)
+            }
+            "owner" => Term::Owner(match value {
+                "me" => None,
+                owner => Some(owner.to_string()),
+            }),
+            "tag" => Term::Tag(tags::normalize(value)?),
+            "due" if value.eq_ignore_ascii_case("none") => Term::NoDue,
+            "due" => Term::Due(cmp, date()?),
+            "created" => Term::Created(cmp, date()?),
+            "text" => Term::Text(value.to_lowercase()),
+            "id" => Term::Id(id()?),
+            "parent" => Term::Parent(id()?),
+            _ => {
+                return Err(invalid(&format!(
+                    "unknown field '{}' (expected {})",
+                    field, "status, is, priority, owner, tag, due, created, text, id or parent"
+                )))
+            }
+        })
+    }
+}
+
+fn invalid(message: &str) -> PmError {
+    PmError::InvalidInput(format!("invalid query: {}", message))
+}
+
+#[cfg(test)]
+mod tests {
+    use std::collections::BTreeMap;
+
+    use super::*;
+
+    fn config(date_format: Option<&str>) -> Config {
+        Config {
+            default_priority: None,
+            date_format: date_format.map(String::from),
+            tag_colors: BTreeMap::new(),
+            queries: BTreeMap::from([
+                ("mine".to_string(), "owner:me is:open".to_string()),
+                ("loop".to_string(), "@loop".to_string()),
+            ]),
+        }
+    }
+
+    fn parse(input: &str) -> Result<Query> {
+        Query::parse(input, &Workflow::default(), &config(None))
+    }
+
+    fn term(term: Term) -> Box<Expr> {
+        Box::new(Expr::Term(term))
+    }
+
+    fn is_invalid(input: &str) -> bool {
+        matches!(parse(input), Err(PmError::InvalidInput(_)))
+    }
+
+    #[test]
+    fn tokenizes_operators_words_and_quotes() {
+        assert_eq!(
+            tokenize(r#"!(a && b) or text:"two words" "x y""#).unwrap(),
+            [
+                Token::Not,
+                Token::Open,
+                Token::Word("a".into()),
+                Token::And,
+                Token::Word("b".into()),
+                Token::Close,
+                Token::Or,
+                Token::Word("text:two words".into()),
+                Token::Quoted("x y".into()),
+            ]
+        );
+        assert!(tokenize(r#"text:"open"#).is_err());
+    }
+
+    #[test]
+    fn adjacent_terms_are_and_and_or_binds_looser() {
+        let query = parse("tag:api or priority>=high due<+3d").unwrap();
+
+        assert_eq!(
+            query.expr,
+            Some(Expr::Or(
+                term(Term::Tag("api".into())),
+                Box::new(Expr::And(
+                    term(Term::Priority(Cmp::Ge, Priority::High)),
+                    term(Term::Due(Cmp::Lt, DateSpec::DaysFromToday(3))),
+                )),
+            ))
+        );
+    }
+
+    #[test]
+    fn expands_saved_queries() {
+        let query = parse("not @mine").unwrap();
+
+        assert_eq!(
+            query.expr,
+            Some(Expr::Not(Box::new(Expr::And(
+                term(Term::Owner(None)),
+                term(Term::Is(State::Open)),
+            ))))
+        );
+        assert!(is_invalid("@loop"));
+        assert!(is_invalid("@unknown"));
+    }
+
+    #[test]
+    fn rejects_malformed_queries() {
+        for input in [
+            "(tag:api",
+            "tag:api)",
+            "and",
+            "tag:",
+            "owner<me",
+            "colour:red",
+            "is:everything",
+            "id:x",
+        ] {
+            assert!(is_invalid(input), "{}", input);
+        }
+        assert!(parse("").unwrap().is_empty());
+    }
+
+    #[test]
+    fn parses_date_specs() {
+        let date = NaiveDate::from_ymd_opt(2024, 3, 1).unwrap();
+        let cfg = config(None);
+        let parse = |s: &str| DateSpec::parse(s, &cfg);
+
+        assert_eq!(parse("2024-03-01"), Some(DateSpec::On(date)));
+        assert_eq!(parse("today"), Some(DateSpec::DaysFromToday(0)));
+        assert_eq!(parse("yesterday"), Some(DateSpec::DaysFromToday(-1)));
+        assert_eq!(parse("+3d"), Some(DateSpec::DaysFromToday(3)));
+        assert_eq!(parse("10d"), Some(DateSpec::DaysFromToday(10)));
+        assert_eq!(parse("-2w"), Some(DateSpec::DaysFromToday(-14)));
+
+        for bad in ["", "d", "+w", "3x", "+-3d", "3 d", "2024-02-30"] {
+            assert_eq!(parse(bad), None, "{}", bad);
+        }
+    }
+
+    #[test]
+    fn reads_dates_in_the_configured_format() {
+        let date = NaiveDate::from_ymd_opt(2024, 3, 1).unwrap();
+        let cfg = config(Some("%d.%m.%Y"));
+        let workflow = Workflow::default();
+
+        assert_eq!(
+            DateSpec::parse("01.03.2024", &cfg),
+            Some(DateSpec::On(date))
+        );
+        assert_eq!(
+            DateSpec::parse("2024-03-01", &cfg),
+            Some(DateSpec::On(date))
+        );
+        assert_eq!(
+            Query::parse("due<=01.03.2024", &workflow, &cfg)
+                .unwrap()
+                .expr,
+            Some(Expr::Term(Term::Due(Cmp::Le, DateSpec::On(date))))
+        );
+        let err = Query::parse("due<03/01/2024", &workflow, &cfg).unwrap_err();
+        assert!(err.to_string().contains("expected %d.%m.%Y"), "{}", err);
+    }
+
+    #[test]
+    fn resolves_date_specs() {
+        let today = NaiveDate::from_ymd_opt(2024, 2, 27).unwrap();
+
+        assert_eq!(
+            DateSpec::DaysFromToday(3).resolve(today),
+            NaiveDate::from_ymd_opt(2024, 3, 1).unwrap()
+        );
+        assert_eq!(
+            DateSpec::DaysFromToday(i64::MAX).resolve(today),
+            NaiveDate::MAX
+        );
+        assert_eq!(
+            DateSpec::DaysFromToday(i64::MIN).resolve(today),
+            NaiveDate::MIN
+        );
+    }
+
+    #[test]
+    fn rejects_out_of_range_and_non_ascii_dates() {
+        for input in [
+            "due<5é",
+            "due<é",
+            "created>+ü",
+            "due<+99999999999d",
+            "due<-99999999999d",
+            "due<+999999999999999w",
+            "due<+9999999999999999999d",
+        ] {
+            assert!(is_invalid(input), "{}", input);
+        }
+    }
+}
@@ -0,0 +1,407 @@
+use std::fmt::Write;
+
+use chrono::{DateTime, Local, NaiveDate};
+
+use crate::config;
+use crate::models::{Config, Project, Status, StatusKind, Task};
+use crate::workflow::Workflow;
+
+/// A project status report, rendered as Markdown or HTML.
+#[derive(Debug, Clone)]
+pub struct Report {
+    pub project: Project,
+    pub generated_at: DateTime<Local>,
+    /// Tasks per status in board order; statuses without tasks are left out
+    /// and statuses the workflow does not know come last.
+    pub groups: Vec<(Status, Vec<Task>)>,
+    /// Open tasks past their deadline, earliest first.
+    pub overdue: Vec<Task>,
+    pub stats: Stats,
+    /// Contents of `notes.md`.
+    pub notes: String,
+}
+
+/// Task counts of a report.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub struct Stats {
+    pub total: usize,
+    pub done: usize,
+    pub cancelled: usize,
+    pub overdue: usize,
+}
+
+impl Stats {
+    pub fn open(&self) -> usize {
+        self.total - self.done - self.cancelled
+    }
+
+    /// Share of done tasks, cancelled ones not counted.
+    pub fn percent_done(&self) -> u32 {
+        match self.total - self.cancelled {
+            0 => 100,
+            counted => (self.done * 100 / counted) as u32,
+        }
+    }
+}
+
+impl Report {
+    pub fn build(
+        project: Project,
+        tasks: Vec<Task>,
+        workflow: &Workflow,
+        notes: String,
+        today: NaiveDate,
+    ) -> Report {
+        let kind = |t: &Task| workflow.kind(&t.status);
+
+        let mut overdue: Vec<Task> = tasks
+            .iter()
+            .filter(|t| !kind(t).is_closed() && t.deadline.is_some_and(|d| d < today))
+            .cloned()
+            .collect();
+        overdue.sort_by_key(|t| t.deadline);
+
+        let stats = Stats {
+            total: tasks.len(),
+            done: tasks.iter().filter(|t| kind(t) == StatusKind::Done).count(),
+            cancelled: tasks
+                .iter()
+                .filter(|t| kind(t) == StatusKind::Cancelled)
+                .count(),
+            overdue: overdue.len(),
+        };
+
+        let mut groups: Vec<(Status, Vec<Task>)> = Vec::new();
+        let mut sorted = tasks;
+        // Statuses missing from the workflow share a position; their names
+        // keep each of them in one group
+        sorted.sort_by_key(|t| (workflow.position(&t.status), t.status.to_string()));
+
+        for task in sorted {
+            match groups.last_mut() {
+                Some((status, group)) if *status == task.status => group.push(task),
+                _ => groups.push((task.status.clone(), vec![task])),
+            }
+        }
+
+        Report {
+            project,
+            generated_at: Local::now(),
+            groups,
+            overdue,
+            stats,
+            notes,
+        }
+    }
+
+    pub fn markdown(&self, cfg: &Config) -> String {
+        let mut out = String::new();
+        let s = &self.stats;
+
+        writeln!(out, "# {}\n", self.project.name).ok();
+        writeln!(
+            out,
+            "Created {} · report generated {}\n",
+            self.project.created_at.format("%Y-%m-%d"),
+            self.generated_at.format("%Y-%m-%d %H:%M")
+        )
+        .ok();
+
+        out.push_str("## Summary\n\n");
+        writeln!(out, "- **{}% complete**", s.percent_done()).ok();
+        writeln!(out, "- Tasks: {}", s.total).ok();
+        writeln!(out, "- Open: {}", s.open()).ok();
+        writeln!(out, "- Done: {}", s.done).ok();
+        writeln!(out, "- Cancelled: {}", s.cancelled).ok();
+        writeln!(out, "- Overdue: {}", s.overdue).ok();
+
+        if !self.overdue.is_empty() {
+            out.push_str("\n## Overdue\n\n");
+            markdown_table(&mut out, &self.overdue, cfg);
+        }
+
+        for (status, tasks) in &self.groups {
+            writeln!(out, "\n## {} ({})\n", status.label(), tasks.len()).ok();
+            markdown_table(&mut out, tasks, cfg);
+        }
+
+        if !self.notes.trim().is_empty() {
+            out.push_str("\n## Notes\n\n");
+            out.push_str(self.notes.trim_end());
+            out.push('\n');
+        }
+
+        out
+    }
+
+    /// A self-contained page with inline styles.
+    pub fn html(&self, cfg: &Config) -> String {
+        let mut out = String::new();
+        let s = &self.stats;
+        let name = escape(&self.project.name);
+
+        writeln!(
+            out,
+            "<!DOCTYPE html>\n<html lang=\"en\">\n<head>\n<meta charset=\"utf-8\">\n\
+             <title>{}</title>\n<style>{}</style>\n</head>\n<body>",
+            name, STYLE
+        )
+        .ok();
+        writeln!(out, "<h1>{}</h1>", name).ok();
+        writeln!(
+            out,
+            "<p class=\"meta\">Created {} · report generated {}</p>",
+            self.project.created_at.format("%Y-%m-%d"),
+            self.generated_at.format("%Y-%m-%d %H:%M")
+        )
+        .ok();
+
+        out.push_str("<h2>Summary</h2>\n");
+        writeln!(
+            out,
+            "<div class=\"progress\"><div style=\"width: {0}%\"></div></div>\n\
+             <p><strong>{0}% complete</strong></p>",
+            s.percent_done()
+        )
+        .ok();
+        writeln!(
+            out,
+            "<ul class=\"stats\"><li>Tasks <b>{}</b></li><li>Open <b>{}</b></li>\
+             <li>Done <b>{}</b></li><li>Cancelled <b>{}</b></li>\
+             <li>Overdue <b>{}</b></li></ul>",
+            s.total,
+            s.open(),
+            s.done,
+            s.cancelled,
+            s.overdue
+        )
+        .ok();
+
+        if !self.overdue.is_empty() {
+            out.push_str("<h2 class=\"overdue\">Overdue</h2>\n");
+            html_table(&mut out, &self.overdue, cfg);
+        }
+
+        for (status, tasks) in &self.groups {
+            writeln!(
+                out,
+                "<h2>{} <small>({})</small></h2>",
+                escape(&status.label()),
+                tasks.len()
+            )
+            .ok();
+            html_table(&mut out, tasks, cfg);
+        }
+
+        if !self.notes.trim().is_empty() {
+            out.push_str("<h2>Notes</h2>\n<ul class=\"notes\">\n");
+            for line in self.notes.lines().filter(|l| !l.trim().is_empty()) {
+                let line = line.strip_prefix("- ").unwrap_or(line);
+                writeln!(out, "<li>{}</li>", escape(line)).ok();
+            }
+            out.push_str("</ul>\n");
+        }
+
+        out.push_str("</body>\n</html>\n");
+        out
+    }
+}
+
+const STYLE: &str = "\
+body{font-family:system-ui,sans-serif;max-width:60em;margin:2em auto;padding:0 1em;color:#222}\
+h1{margin-bottom:0}h2{border-bottom:1px solid #ddd;padding-bottom:.2em}\
+h2 small{color:#888;font-weight:normal}.meta{color:#666}\
+.overdue{color:#b00020}\
+table{border-collapse:collapse;width:100%}\
+th,td{text-align:left;padding:.3em .6em;border-bottom:1px solid #eee}\
+th{background:#f5f5f5}td.id{color:#888;width:3em}\
+.progress{background:#eee;border-radius:4px;height:10px;max-width:20em}\
+.progress div{background:#2e7d32;height:100%;border-radius:4px}\
+.stats{list-style:none;padding:0;display:flex;gap:1.5em}\
+.notes li{margin:.2em 0}";
+
+fn deadline(task: &Task, cfg: &Config) -> String {
+    task.deadline
+        .map(|d| config::format_date(cfg, d))
+        .unwrap_or_default()
+}
+
+fn markdown_table(out: &mut String, tasks: &[Task], cfg: &Config) {
+    // Pipes and line breaks would end the cell
+    let cell = |s: &str| s.replace('|', "\\|").replace(['\r', '\n'], " ");
+
+    out.push_str("| ID | Task | Priority | Deadline | Owner |\n");
+    out.push_str("|---:|------|----------|----------|-------|\n");
+
+    for t in tasks {
+        writeln!(
+            out,
+            "| {} | {} | {} | {} | {} |",
+            t.id,
+            cell(&t.description),
+            t.priority,
+            deadline(t, cfg),
+            cell(&t.owner)
+        )
+        .ok();
+    }
+}
+
+fn html_table(out: &mut String, tasks: &[Task], cfg: &Config) {
+    out.push_str(
+        "<table>\n<tr><th>ID</th><th>Task</th><th>Priority</th>\
+         <th>Deadline</th><th>Owner</th></tr>\n",
+    );
+
+    for t in tasks {
+        writeln!(
+            out,
+            "<tr><td class=\"id\">{}</td><td>{}</td><td>{}</td><td>{}</td><td>{}</td></tr>",
+            t.id,
+            escape(&t.description),
+            t.priority,
+            deadline(t, cfg),
+            escape(&t.owner)
+        )
+        .ok();
+    }
+
+    out.push_str("</table>\n");
+}
+
+fn escape(s: &str) -> String {
+    s.replace('&', "&amp;")
+        .replace('<', "&lt;")
+        .replace('>', "&gt;")
+        .replace('"', "&quot;")
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::test_support::{self, config};
+
+    fn task(id: u32, status: Status, deadline: Option<&str>) -> Task {
+        Task {
+            description: format!("Task <{}> | x", id),
+            deadline: deadline.map(|d| d.parse().unwrap()),
+            ..test_support::task(id, status)
+        }
+    }
+
+    fn report(tasks: Vec<Task>) -> Report {
+        let project = Project {
+            name: "web & co".into(),
+            created_at: Local::now(),
+            next_task_id: 1,
+        };
+        let today = NaiveDate::from_ymd_opt(2024, 3, 10).unwrap();
+
+        Report::build(
+            project,
+            tasks,
+            &Workflow::default(),
+            "- ship it\n".into(),
+            today,
+        )
+    }
+
+    #[test]
+    fn counts_and_groups_tasks() {
+        let report = report(vec![
+            task(1, Status::Done, Some("2024-03-01")),
+            task(2, Status::Custom("Limbo".into()), None),
+            task(3, Status::Todo, Some("2024-03-09")),
+            task(4, Status::Cancelled, None),
+            task(5, Status::InProgress, Some("2024-03-05")),
+            task(6, Status::Todo, Some("2024-03-10")),
+        ]);
+
+        assert_eq!(
+            report.stats,
+            Stats {
+                total: 6,
+                done: 1,
+                cancelled: 1,
+                overdue: 2,
+            }
+        );
+        assert_eq!(report.stats.open(), 4);
+        assert_eq!(report.stats.percent_done(), 20);
+
+        let overdue: Vec<u32> = report.overdue.iter().map(|t| t.id).collect();
+        assert_eq!(overdue, [5, 3]);
+
+        let groups: Vec<(String, Vec<u32>)> = report
+            .groups
+            .iter()
+            .map(|(s, tasks)| (s.to_string(), tasks.iter().map(|t| t.id).collect()))
+            .collect();
+        assert_eq!(
+            groups,
+            [
+                ("todo".to_string(), vec![3, 6]),
+                ("in-progress".to_string(), vec![5]),
+                ("done".to_string(), vec![1]),
+                ("cancelled".to_string(), vec![4]),
+                ("Limbo".to_string(), vec![2]),
+            ]
+        );
+    }
+
+    #[test]
+    fn groups_each_unknown_status_once() {
+        let limbo = || Status::Custom("Limbo".into());
+        let parked = || Status::Custom("Parked".into());
+        let report = report(vec![
+            task(1, limbo(), None),
+            task(2, parked(), None),
+            task(3, limbo(), None),
+            task(4, Status::Todo, None),
+            task(5, parked(), None),
+        ]);
+
+        let groups: Vec<(String, Vec<u32>)> = report
+            .groups
+            .iter()
+            .map(|(s, tasks)| (s.to_string(), tasks.iter().map(|t| t.id).collect()))
+            .collect();
+        assert_eq!(
+            groups,
+            [
+                ("todo".to_string(), vec![4]),
+                ("Limbo".to_string(), vec![1, 3]),
+                ("Parked".to_string(), vec![2, 5]),
+            ]
+        );
+    }
+
+    #[test]
+    fn empty_or_cancelled_projects_are_complete() {
+        assert_eq!(report(Vec::new()).stats.percent_done(), 100);
+        assert_eq!(
+            report(vec![task(1, Status::Cancelled, None)])
+                .stats
+                .percent_done(),
+            100
+        );
+    }
+
+    #[test]
+    fn renders_markdown_and_escaped_html() {
+        let report = report(vec![task(1, Status::Todo, Some("2024-03-01"))]);
+
+        let markdown = report.markdown(&config());
+        assert!(markdown.starts_with("# web & co\n"));
+        assert!(markdown.contains("- **0% complete**\n"));
+        assert!(markdown.contains("## Overdue\n"));
+        assert!(markdown.contains("| 1 | Task <1> \\| x | medium | 2024-03-01 | me |\n"));
+        assert!(markdown.ends_with("## Notes\n\n- ship it\n"));
+
+        let html = report.html(&config());
+        assert!(html.contains("<h1>web &amp; co</h1>"));
+        assert!(html.contains("<td>Task &lt;1&gt; | x</td>"));
+        assert!(html.contains("<li>ship it</li>"));
+        assert!(!html.contains("<1>"));
+    }
+}
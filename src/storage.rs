@@ -0,0 +1,1057 @@
+use std::collections::{HashMap, HashSet};
+use std::fmt;
+use std::fs::{self, File, OpenOptions};
+use std::io::{self, Write};
+use std::ops::Range;
+use std::path::{Path, PathBuf};
+use std::thread;
+use std::time::{Duration, Instant, SystemTime};
+
+use chrono::Local;
+use dirs::home_dir;
+use serde::de::DeserializeOwned;
+use serde::Serialize;
+
+use crate::bundle::{self, Bundle};
+use crate::config;
+use crate::history::{self, HistoryEntry};
+use crate::journal::{self, Journal, JournalEntry, Operation, TaskChange};
+use crate::models::{Project, StatusKind, Task};
+use crate::workflow::Workflow;
+
+const PROJECT_FILE: &str = "project.json";
+const TASKS_FILE: &str = "tasks.json";
+const NOTES_FILE: &str = "notes.md";
+const ARCHIVE_FILE: &str = "archive.json";
+const WORKFLOW_FILE: &str = "workflow.toml";
+const HISTORY_FILE: &str = "history.jsonl";
+const JOURNAL_FILE: &str = "journal.json";
+const LOCK_FILE: &str = ".lock";
+
+/// How long to wait for another process to release the project lock.
+const LOCK_TIMEOUT: Duration = Duration::from_secs(5);
+/// Lock files older than this are assumed to be left over from a crash.
+const LOCK_STALE_AFTER: Duration = Duration::from_secs(30);
+
+#[derive(Debug)]
+pub enum StorageError {
+    NoHomeDir,
+    InvalidName(String),
+    ProjectNotFound(String),
+    ProjectExists(String),
+    Locked(PathBuf),
+    Io {
+        path: PathBuf,
+        source: io::Error,
+    },
+    Parse {
+        path: PathBuf,
+        source: serde_json::Error,
+    },
+    Invalid {
+        path: PathBuf,
+        message: String,
+    },
+}
+
+impl fmt::Display for StorageError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            StorageError::NoHomeDir => write!(f, "cannot locate home directory"),
+            StorageError::InvalidName(name) => write!(f, "invalid project name '{}'", name),
+            StorageError::ProjectNotFound(name) => write!(f, "project '{}' not found", name),
+            StorageError::ProjectExists(name) => write!(f, "project '{}' already exists", name),
+            StorageError::Locked(path) => write!(
+                f,
+                "project is locked by another pmcli process (remove {} if it crashed)",
+                path.display()
+            ),
+            StorageError::Io { path, source } => write!(f, "{}: {}", path.display(), source),
+            StorageError::Parse { path, source } => {
+                write!(f, "{}: invalid data ({})", path.display(), source)
+            }
+            StorageError::Invalid { path, message } => write!(f, "{}: {}", path.display(), message),
+        }
+    }
+}
+
+impl std::error::Error for StorageError {
+    fn source(&self) -> Option<&(dyn std::error::Error + 'static)> {
+        match self {
+            StorageError::Io { source, .. } => Some(source),
+            StorageError::Parse { source, .. } => Some(source),
+            _ => None,
+        }
+    }
+}
+
+pub type Result<T> = std::result::Result<T, StorageError>;
+
+/// Root directory holding every project (`~/.pmcli`).
+pub fn base_dir() -> Result<PathBuf> {
+    home_dir()
+        .map(|h| h.join(".pmcli"))
+        .ok_or(StorageError::NoHomeDir)
+}
+
+/// Handle to a single project directory (`~/.pmcli/<name>`).
+///
+/// All reads and writes of project files go through this type so the
+/// on-disk layout lives in one place.
+pub struct ProjectStore {
+    name: String,
+    dir: PathBuf,
+}
+
+/// Advisory lock on a project directory, released on drop.
+///
+/// Only pmcli honours it; it guards read-modify-write cycles against other
+/// pmcli processes working on the same project.
+pub struct ProjectLock {
+    path: PathBuf,
+}
+
+impl Drop for ProjectLock {
+    fn drop(&mut self) {
+        fs::remove_file(&self.path).ok();
+    }
+}
+
+/// Modification stamp of a data file, used to notice changes made by
+/// another process.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct FileStamp {
+    modified: Option<SystemTime>,
+    len: u64,
+}
+
+/// Timestamps an update sets on the tasks it changed.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum Stamp {
+    /// `updated_at`, and `completed_at` on status changes, as of now.
+    All,
+    /// Only the ones still empty, for imported tasks.
+    Missing,
+    /// None, for replaying the journal.
+    None,
+}
+
+impl ProjectStore {
+    /// Open an existing project.
+    pub fn open(name: &str) -> Result<Self> {
+        let store = Self::at(name)?;
+
+        if !store.dir.join(PROJECT_FILE).exists() {
+            return Err(StorageError::ProjectNotFound(name.to_string()));
+        }
+
+        Ok(store)
+    }
+
+    /// Create a new project with empty task list and notes.
+    pub fn create(name: &str) -> Result<Self> {
+        let store = Self::at(name)?;
+
+        if store.dir.exists() {
+            return Err(StorageError::ProjectExists(name.to_string()));
+        }
+
+        fs::create_dir_all(&store.dir).map_err(|e| io_err(&store.dir, e))?;
+
+        store.save_project(&Project {
+            name: name.to_string(),
+            created_at: Local::now(),
+            next_task_id: 1,
+        })?;
+        store.save_tasks(&[])?;
+        store.write(NOTES_FILE, "")?;
+
+        Ok(store)
+    }
+
+    /// Metadata of every project under the base directory.
+    pub fn list() -> Result<Vec<Project>> {
+        let base = base_dir()?;
+        if !base.exists() {
+            return Ok(Vec::new());
+        }
+
+        let mut projects = Vec::new();
+
+        for entry in fs::read_dir(&base).map_err(|e| io_err(&base, e))? {
+            let dir = entry.map_err(|e| io_err(&base, e))?.path();
+            let meta_path = dir.join(PROJECT_FILE);
+
+            if meta_path.exists() {
+                projects.push(read_json(&meta_path)?);
+            }
+        }
+
+        Ok(projects)
+    }
+
+    fn at(name: &str) -> Result<Self> {
+        if name.is_empty() || name == "." || name == ".." || name.contains(['/', '\\']) {
+            return Err(StorageError::InvalidName(name.to_string()));
+        }
+
+        Ok(Self {
+            name: name.to_string(),
+            dir: base_dir()?.join(name),
+        })
+    }
+
+    /// Store for a project directory outside the base directory.
+    #[cfg(test)]
+    pub(crate) fn at_dir(name: &str, dir: PathBuf) -> Self {
+        Self {
+            name: name.to_string(),
+            dir,
+        }
+    }
+
+    pub fn name(&self) -> &str {
+        &self.name
+    }
+
+    pub fn dir(&self) -> &Path {
+        &self.dir
+    }
+
+    pub fn load_project(&self) -> Result<Project> {
+        read_json(&self.dir.join(PROJECT_FILE))
+    }
+
+    pub fn save_project(&self, project: &Project) -> Result<()> {
+        self.write_json(PROJECT_FILE, project)
+    }
+
+    /// Take the project lock, waiting for other pmcli processes to finish.
+    pub fn lock(&self) -> Result<ProjectLock> {
+        let path = self.dir.join(LOCK_FILE);
+        let started = Instant::now();
+
+        loop {
+            match OpenOptions::new().write(true).create_new(true).open(&path) {
+                Ok(mut file) => {
+                    writeln!(file, "{}", std::process::id()).ok();
+                    return Ok(ProjectLock { path });
+                }
+                Err(e) if e.kind() == io::ErrorKind::AlreadyExists => {
+                    if lock_is_stale(&path) {
+                        remove_stale_lock(&path);
+                        continue;
+                    }
+                    if started.elapsed() >= LOCK_TIMEOUT {
+                        return Err(StorageError::Locked(path));
+                    }
+                    thread::sleep(Duration::from_millis(50));
+                }
+                Err(e) => return Err(io_err(&path, e)),
+            }
+        }
+    }
+
+    /// Load, modify and save the task list under the project lock.
+    ///
+    /// The file is only rewritten when `f` actually changed something; an
+    /// error from `f` leaves it untouched.
+    pub fn update_tasks<T>(
+        &self,
+        f: impl FnOnce(&mut Vec<Task>) -> crate::error::Result<T>,
+    ) -> crate::error::Result<T> {
+        self.update_with_archive(|tasks, _| f(tasks))
+    }
+
+    /// Like [`update_tasks`](Self::update_tasks), but also hands out the
+    /// archived tasks so tasks can be moved between both lists.
+    ///
+    /// Changed tasks get `updated_at` stamped, and `completed_at` set or
+    /// cleared when their status changed, depending on whether the new status
+    /// is a done status of the workflow. Status, priority and owner changes,
+    /// new tasks and deletions are appended to `history.jsonl`, and the whole
+    /// change is recorded in `journal.json` so it can be undone. Nothing is
+    /// changed while two tasks share an ID; `repair` fixes that first.
+    pub fn update_with_archive<T>(
+        &self,
+        f: impl FnOnce(&mut Vec<Task>, &mut Vec<Task>) -> crate::error::Result<T>,
+    ) -> crate::error::Result<T> {
+        self.update_journaled(f, Stamp::All)
+    }
+
+    /// Like [`update_with_archive`](Self::update_with_archive), for tasks
+    /// brought in from another file: the timestamps `f` gives changed tasks
+    /// are kept, and only missing ones are set.
+    pub fn import_with_archive<T>(
+        &self,
+        f: impl FnOnce(&mut Vec<Task>, &mut Vec<Task>) -> crate::error::Result<T>,
+    ) -> crate::error::Result<T> {
+        self.update_journaled(f, Stamp::Missing)
+    }
+
+    fn update_journaled<T>(
+        &self,
+        f: impl FnOnce(&mut Vec<Task>, &mut Vec<Task>) -> crate::error::Result<T>,
+        stamp: Stamp,
+    ) -> crate::error::Result<T> {
+        let _lock = self.lock()?;
+
+        let (value, changes) = self.update_locked(f, stamp)?;
+
+        if !changes.is_empty() {
+            let mut journal = self.load_journal()?;
+            journal.record(Operation::Tasks { changes });
+            self.save_journal(&journal)?;
+        }
+
+        Ok(value)
+    }
+
+    /// Body of [`update_with_archive`](Self::update_with_archive) without
+    /// journaling; returns what changed. Call it while holding the project
+    /// lock.
+    fn update_locked<T>(
+        &self,
+        f: impl FnOnce(&mut Vec<Task>, &mut Vec<Task>) -> crate::error::Result<T>,
+        stamp: Stamp,
+    ) -> crate::error::Result<(T, Vec<TaskChange>)> {
+        let workflow = self.load_workflow()?;
+        let mut tasks = self.load_tasks()?;
+        let mut archive = self.load_archive()?;
+
+        // Changes are tracked by ID, so two tasks sharing one would be
+        // mistaken for each other
+        if let Some(id) = first_duplicate_id(&tasks, &archive) {
+            return Err(StorageError::Invalid {
+                path: self.dir.join(TASKS_FILE),
+                message: format!(
+                    "task ID {} is used by more than one task; run `pmcli repair {}` first",
+                    id, self.name
+                ),
+            }
+            .into());
+        }
+
+        let tasks_before = to_json(&self.dir.join(TASKS_FILE), &tasks)?;
+        let archive_before = to_json(&self.dir.join(ARCHIVE_FILE), &archive)?;
+        let mut before: HashMap<u32, Task> = tasks
+            .iter()
+            .chain(archive.iter())
+            .map(|t| (t.id, t.clone()))
+            .collect();
+
+        let value = f(&mut tasks, &mut archive)?;
+
+        let now = Local::now();
+        let user = config::current_user();
+        let mut entries = Vec::new();
+        let mut changes = Vec::new();
+
+        for task in tasks.iter_mut().chain(archive.iter_mut()) {
+            let old = before.remove(&task.id);
+            if old.as_ref() == Some(task) {
+                continue;
+            }
+
+            let done = workflow.kind(&task.status) == StatusKind::Done;
+            match stamp {
+                Stamp::All => {
+                    if old.as_ref().map(|o| &o.status) != Some(&task.status) {
+                        task.completed_at = done.then_some(now);
+                    }
+                    if old.is_none() && task.created_at.is_none() {
+                        task.created_at = Some(now);
+                    }
+                    task.updated_at = Some(now);
+                }
+                Stamp::Missing => {
+                    if done && task.completed_at.is_none() {
+                        task.completed_at = Some(now);
+                    }
+                    if old.is_none() && task.created_at.is_none() {
+                        task.created_at = Some(now);
+                    }
+                    task.updated_at.get_or_insert(now);
+                }
+                Stamp::None => {}
+            }
+
+            entries.extend(history::changes(old.as_ref(), task, &user, now));
+            changes.push(TaskChange {
+                id: task.id,
+                before: old,
+                after: Some(task.clone()),
+            });
+        }
+
+        // Whatever is left in `before` no longer exists
+        let mut deleted: Vec<Task> = before.into_values().collect();
+        deleted.sort_by_key(|t| t.id);
+        for task in deleted {
+            entries.push(history::deleted(&task, &user, now));
+            changes.push(TaskChange {
+                id: task.id,
+                before: Some(task),
+                after: None,
+            });
+        }
+
+        if to_json(&self.dir.join(ARCHIVE_FILE), &archive)? != archive_before {
+            self.save_archive(&archive)?;
+        }
+        if to_json(&self.dir.join(TASKS_FILE), &tasks)? != tasks_before {
+            self.save_tasks(&tasks)?;
+        }
+        self.append_history(&entries)?;
+
+        Ok((value, changes))
+    }
+
+    /// Revert the most recent journaled operation and return it, or `None`
+    /// when there is nothing to undo.
+    pub fn undo(&self) -> crate::error::Result<Option<JournalEntry>> {
+        self.replay(true)
+    }
+
+    /// Make the most recently undone operation again.
+    pub fn redo(&self) -> crate::error::Result<Option<JournalEntry>> {
+        self.replay(false)
+    }
+
+    fn replay(&self, undo: bool) -> crate::error::Result<Option<JournalEntry>> {
+        let _lock = self.lock()?;
+        let mut journal = self.load_journal()?;
+
+        let (from, to) = if undo {
+            (&mut journal.undo, &mut journal.redo)
+        } else {
+            (&mut journal.redo, &mut journal.undo)
+        };
+
+        let Some(entry) = from.pop() else {
+            return Ok(None);
+        };
+
+        // Tasks are put back exactly as recorded, timestamps included, so
+        // the neighbouring entries still match
+        match &entry.operation {
+            Operation::Tasks { changes } => {
+                self.update_locked(
+                    |tasks, archive| journal::apply(tasks, archive, changes, undo),
+                    Stamp::None,
+                )?;
+            }
+            Operation::Note { text } if undo => self.remove_note(text)?,
+            Operation::Note { text } => self.write_note(text)?,
+        }
+
+        to.push(entry.clone());
+        self.save_journal(&journal)?;
+
+        Ok(Some(entry))
+    }
+
+    /// Current stamps of the files task listings are built from:
+    /// `tasks.json`, `archive.json` and `workflow.toml`.
+    pub fn tasks_stamp(&self) -> [FileStamp; 3] {
+        [TASKS_FILE, ARCHIVE_FILE, WORKFLOW_FILE].map(|file| self.stamp(file))
+    }
+
+    fn stamp(&self, file: &str) -> FileStamp {
+        let meta = fs::metadata(self.dir.join(file)).ok();
+
+        FileStamp {
+            modified: meta.as_ref().and_then(|m| m.modified().ok()),
+            len: meta.map(|m| m.len()).unwrap_or(0),
+        }
+    }
+
+    /// Reserve the next task ID and persist the counter in `project.json`.
+    ///
+    /// Projects created before the counter existed start after the highest
+    /// ID already present in `tasks`. Call it while holding the project lock.
+    pub fn allocate_task_id(&self, tasks: &[Task]) -> Result<u32> {
+        Ok(self.allocate_task_ids(tasks, 1)?.start)
+    }
+
+    /// Reserve `count` consecutive task IDs at once, as for an import.
+    pub fn allocate_task_ids(&self, tasks: &[Task], count: u32) -> Result<Range<u32>> {
+        let mut project = self.load_project()?;
+
+        let start = project
+            .next_task_id
+            .max(next_free_id(tasks))
+            .max(next_free_id(&self.load_archive()?));
+        let end = start
+            .checked_add(count)
+            .ok_or_else(|| StorageError::Invalid {
+                path: self.dir.join(PROJECT_FILE),
+                message: "no task IDs left".into(),
+            })?;
+        project.next_task_id = end;
+
+        self.save_project(&project)?;
+        Ok(start..end)
+    }
+
+    /// Renumber tasks whose ID duplicates an earlier task.
+    ///
+    /// The first task with a given ID keeps it (active tasks before archived
+    /// ones); later ones get fresh IDs from the project counter. Returns
+    /// `(old, new)` pairs in file order.
+    ///
+    /// Refuses to renumber anything while a task's parent or dependency is a
+    /// duplicated ID, as there is no telling which of the tasks it meant.
+    pub fn repair_task_ids(&self) -> Result<Vec<(u32, u32)>> {
+        let _lock = self.lock()?;
+        let mut project = self.load_project()?;
+        let mut tasks = self.load_tasks()?;
+        let mut archive = self.load_archive()?;
+
+        let mut ids = HashSet::new();
+        let duplicates: HashSet<u32> = tasks
+            .iter()
+            .chain(&archive)
+            .map(|t| t.id)
+            .filter(|id| !ids.insert(*id))
+            .collect();
+        let linked = tasks.iter().chain(&archive).find_map(|t| {
+            t.parent
+                .iter()
+                .chain(&t.depends_on)
+                .find(|id| duplicates.contains(id))
+                .map(|id| (t.id, *id))
+        });
+        if let Some((task, id)) = linked {
+            return Err(StorageError::Invalid {
+                path: self.dir.join(TASKS_FILE),
+                message: format!(
+                    "task {} links to task {}, which is used by more than one task; \
+                     fix the link by hand before repairing",
+                    task, id
+                ),
+            });
+        }
+
+        let mut next = project
+            .next_task_id
+            .max(next_free_id(&tasks))
+            .max(next_free_id(&archive));
+        let mut seen = HashSet::new();
+        let mut mapping = Vec::new();
+
+        for task in tasks.iter_mut().chain(archive.iter_mut()) {
+            if !seen.insert(task.id) {
+                mapping.push((task.id, next));
+                task.id = next;
+                seen.insert(next);
+                next += 1;
+            }
+        }
+
+        if !mapping.is_empty() {
+            self.save_tasks(&tasks)?;
+            self.save_archive(&archive)?;
+        }
+
+        if project.next_task_id != next {
+            project.next_task_id = next;
+            self.save_project(&project)?;
+        }
+
+        Ok(mapping)
+    }
+
+    pub fn load_tasks(&self) -> Result<Vec<Task>> {
+        read_json(&self.dir.join(TASKS_FILE))
+    }
+
+    pub fn save_tasks(&self, tasks: &[Task]) -> Result<()> {
+        self.write_json(TASKS_FILE, &tasks)
+    }
+
+    /// Archived tasks; empty when the project has never archived anything.
+    pub fn load_archive(&self) -> Result<Vec<Task>> {
+        let path = self.dir.join(ARCHIVE_FILE);
+
+        if !path.exists() {
+            return Ok(Vec::new());
+        }
+
+        read_json(&path)
+    }
+
+    pub fn save_archive(&self, tasks: &[Task]) -> Result<()> {
+        self.write_json(ARCHIVE_FILE, &tasks)
+    }
+
+    /// The project's workflow, or the built-in one when `workflow.toml` does
+    /// not exist.
+    pub fn load_workflow(&self) -> Result<Workflow> {
+        let path = self.dir.join(WORKFLOW_FILE);
+
+        let data = match fs::read_to_string(&path) {
+            Ok(data) => data,
+            Err(e) if e.kind() == io::ErrorKind::NotFound => return Ok(Workflow::default()),
+            Err(e) => return Err(io_err(&path, e)),
+        };
+
+        let workflow: Workflow = toml::from_str(&data).map_err(|e| StorageError::Invalid {
+            path: path.clone(),
+            message: e.message().to_string(),
+        })?;
+
+        workflow
+            .validate()
+            .map_err(|message| StorageError::Invalid { path, message })?;
+
+        Ok(workflow)
+    }
+
+    pub fn save_workflow(&self, workflow: &Workflow) -> Result<()> {
+        let path = self.dir.join(WORKFLOW_FILE);
+
+        let data = toml::to_string_pretty(workflow).map_err(|e| StorageError::Invalid {
+            path,
+            message: e.to_string(),
+        })?;
+
+        self.write(WORKFLOW_FILE, &data)
+    }
+
+    /// Every recorded change, oldest first.
+    pub fn load_history(&self) -> Result<Vec<HistoryEntry>> {
+        let path = self.dir.join(HISTORY_FILE);
+
+        let data = match fs::read_to_string(&path) {
+            Ok(data) => data,
+            Err(e) if e.kind() == io::ErrorKind::NotFound => return Ok(Vec::new()),
+            Err(e) => return Err(io_err(&path, e)),
+        };
+
+        data.lines()
+            .filter(|line| !line.trim().is_empty())
+            .map(|line| {
+                serde_json::from_str(line).map_err(|source| StorageError::Parse {
+                    path: path.clone(),
+                    source,
+                })
+            })
+            .collect()
+    }
+
+    /// Append entries to `history.jsonl`. Call it while holding the project
+    /// lock.
+    fn append_history(&self, entries: &[HistoryEntry]) -> Result<()> {
+        if entries.is_empty() {
+            return Ok(());
+        }
+
+        let path = self.dir.join(HISTORY_FILE);
+        let mut lines = String::new();
+
+        for entry in entries {
+            let line = serde_json::to_string(entry).map_err(|source| StorageError::Parse {
+                path: path.clone(),
+                source,
+            })?;
+            lines.push_str(&line);
+            lines.push('\n');
+        }
+
+        OpenOptions::new()
+            .create(true)
+            .append(true)
+            .open(&path)
+            .and_then(|mut file| file.write_all(lines.as_bytes()))
+            .map_err(|e| io_err(&path, e))
+    }
+
+    /// Contents of `notes.md`; empty when it does not exist.
+    pub fn load_notes(&self) -> Result<String> {
+        let path = self.dir.join(NOTES_FILE);
+
+        match fs::read_to_string(&path) {
+            Ok(notes) => Ok(notes),
+            Err(e) if e.kind() == io::ErrorKind::NotFound => Ok(String::new()),
+            Err(e) => Err(io_err(&path, e)),
+        }
+    }
+
+    /// Replace `notes.md`. Call it while holding the project lock.
+    pub fn save_notes(&self, notes: &str) -> Result<()> {
+        self.write(NOTES_FILE, notes)
+    }
+
+    pub fn append_note(&self, note: &str) -> Result<()> {
+        let _lock = self.lock()?;
+
+        self.write_note(note)?;
+
+        let mut journal = self.load_journal()?;
+        journal.record(Operation::Note {
+            text: note.to_string(),
+        });
+        self.save_journal(&journal)
+    }
+
+    fn write_note(&self, note: &str) -> Result<()> {
+        let path = self.dir.join(NOTES_FILE);
+
+        let mut file = OpenOptions::new()
+            .create(true)
+            .append(true)
+            .open(&path)
+            .map_err(|e| io_err(&path, e))?;
+
+        writeln!(file, "- {}", note).map_err(|e| io_err(&path, e))
+    }
+
+    /// Drop `note` from the end of `notes.md`.
+    fn remove_note(&self, note: &str) -> Result<()> {
+        let path = self.dir.join(NOTES_FILE);
+        let notes = fs::read_to_string(&path).map_err(|e| io_err(&path, e))?;
+
+        match notes.strip_suffix(&format!("- {}\n", note)) {
+            Some(rest) => self.write(NOTES_FILE, rest),
+            None => Err(StorageError::Invalid {
+                path,
+                message: "note is no longer the last line, cannot undo".into(),
+            }),
+        }
+    }
+
+    /// The whole project as a [`Bundle`].
+    pub fn load_bundle(&self) -> Result<Bundle> {
+        let workflow = if self.dir.join(WORKFLOW_FILE).exists() {
+            Some(self.load_workflow()?)
+        } else {
+            None
+        };
+
+        Ok(Bundle {
+            format: bundle::FORMAT.to_string(),
+            version: bundle::VERSION,
+            exported_at: Local::now(),
+            project: self.load_project()?,
+            tasks: self.load_tasks()?,
+            archive: self.load_archive()?,
+            notes: self.load_notes()?,
+            workflow,
+            history: self.load_history()?,
+        })
+    }
+
+    /// Replace the project's contents with `bundle`, keeping this project's
+    /// name. The undo journal is dropped as it describes the old contents.
+    pub fn restore_bundle(&self, bundle: &Bundle) -> Result<()> {
+        let _lock = self.lock()?;
+
+        self.save_project(&Project {
+            name: self.name.clone(),
+            ..bundle.project.clone()
+        })?;
+        self.save_tasks(&bundle.tasks)?;
+        self.save_archive(&bundle.archive)?;
+        self.save_notes(&bundle.notes)?;
+
+        match &bundle.workflow {
+            Some(workflow) => self.save_workflow(workflow)?,
+            None => self.remove(WORKFLOW_FILE)?,
+        }
+
+        self.remove(HISTORY_FILE)?;
+        self.append_history(&bundle.history)?;
+        self.remove(JOURNAL_FILE)
+    }
+
+    /// Undo/redo stacks; empty when `journal.json` does not exist.
+    pub fn load_journal(&self) -> Result<Journal> {
+        let path = self.dir.join(JOURNAL_FILE);
+
+        if !path.exists() {
+            return Ok(Journal::default());
+        }
+
+        read_json(&path)
+    }
+
+    fn save_journal(&self, journal: &Journal) -> Result<()> {
+        self.write_json(JOURNAL_FILE, journal)
+    }
+
+    fn write_json<T: Serialize + ?Sized>(&self, file: &str, value: &T) -> Result<()> {
+        let data = to_json(&self.dir.join(file), value)?;
+        self.write(file, &data)
+    }
+
+    fn write(&self, file: &str, data: &str) -> Result<()> {
+        write_atomic(&self.dir.join(file), data)
+    }
+
+    /// Delete `file` if it exists.
+    fn remove(&self, file: &str) -> Result<()> {
+        let path = self.dir.join(file);
+
+        match fs::remove_file(&path) {
+            Err(e) if e.kind() != io::ErrorKind::NotFound => Err(io_err(&path, e)),
+            _ => Ok(()),
+        }
+    }
+}
+
+/// Write `data` to a temporary file next to `path` and rename it into place,
+/// so readers never observe a half-written file.
+pub fn write_atomic(path: &Path, data: &str) -> Result<()> {
+    let name = path.file_name().and_then(|n| n.to_str()).unwrap_or("data");
+    let tmp = path.with_file_name(format!(".{}.tmp", name));
+
+    let written = File::create(&tmp).and_then(|mut f| {
+        f.write_all(data.as_bytes())?;
+        f.sync_all()
+    });
+
+    if let Err(e) = written.and_then(|_| fs::rename(&tmp, path)) {
+        fs::remove_file(&tmp).ok();
+        return Err(io_err(path, e));
+    }
+
+    Ok(())
+}
+
+/// Remove a lock left over from a crash without racing other waiters.
+///
+/// The lock is renamed away first, which only one process can do. If the
+/// renamed file turns out to be fresh, another waiter removed the stale lock
+/// and took the project in the meantime, so it is put back.
+fn remove_stale_lock(path: &Path) {
+    let taken = path.with_file_name(format!(".lock.{}.tmp", std::process::id()));
+
+    if fs::rename(path, &taken).is_err() {
+        return;
+    }
+    if !lock_is_stale(&taken) {
+        fs::hard_link(&taken, path).ok();
+    }
+    fs::remove_file(&taken).ok();
+}
+
+fn lock_is_stale(path: &Path) -> bool {
+    fs::metadata(path)
+        .and_then(|m| m.modified())
+        .ok()
+        .and_then(|t| t.elapsed().ok())
+        .is_some_and(|age| age > LOCK_STALE_AFTER)
+}
+
+fn to_json<T: Serialize + ?Sized>(path: &Path, value: &T) -> Result<String> {
+    serde_json::to_string_pretty(value).map_err(|source| StorageError::Parse {
+        path: path.to_path_buf(),
+        source,
+    })
+}
+
+fn first_duplicate_id(tasks: &[Task], archive: &[Task]) -> Option<u32> {
+    let mut ids = HashSet::new();
+    tasks
+        .iter()
+        .chain(archive)
+        .map(|t| t.id)
+        .find(|id| !ids.insert(*id))
+}
+
+fn next_free_id(tasks: &[Task]) -> u32 {
+    tasks.iter().map(|t| t.id).max().unwrap_or(0) + 1
+}
+
+fn read_json<T: DeserializeOwned>(path: &Path) -> Result<T> {
+    let data = fs::read_to_string(path).map_err(|e| io_err(path, e))?;
+
+    serde_json::from_str(&data).map_err(|source| StorageError::Parse {
+        path: path.to_path_buf(),
+        source,
+    })
+}
+
+fn io_err(path: &Path, source: io::Error) -> StorageError {
+    StorageError::Io {
+        path: path.to_path_buf(),
+        source,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use chrono::DateTime;
+
+    use super::*;
+    use crate::test_support::{self, temp_dir};
+
+    /// A project in a temporary directory with `tasks`, given as JSON.
+    fn temp_store(name: &str, tasks: &str) -> ProjectStore {
+        let store = test_support::temp_store(name);
+        fs::write(
+            store.dir().join(PROJECT_FILE),
+            r#"{"name":"test","created_at":"2024-01-01T00:00:00Z","next_task_id":3}"#,
+        )
+        .unwrap();
+        fs::write(store.dir().join(TASKS_FILE), tasks).unwrap();
+        store
+    }
+
+    fn task(id: u32, links: &str) -> String {
+        format!(
+            r#"{{"id":{},"description":"t","status":"Todo","priority":"medium","deadline":null,"owner":"me"{}}}"#,
+            id, links
+        )
+    }
+
+    #[test]
+    fn repair_renumbers_duplicates_and_keeps_links() {
+        let store = temp_store(
+            "repair",
+            &format!(
+                "[{},{},{}]",
+                task(1, ""),
+                task(2, ""),
+                task(2, r#","parent":1,"depends_on":[1]"#)
+            ),
+        );
+
+        assert_eq!(store.repair_task_ids().unwrap(), vec![(2, 3)]);
+
+        let tasks = store.load_tasks().unwrap();
+        let ids: Vec<u32> = tasks.iter().map(|t| t.id).collect();
+        assert_eq!(ids, [1, 2, 3]);
+        assert_eq!(tasks[2].parent, Some(1));
+        assert_eq!(tasks[2].depends_on, [1]);
+        assert_eq!(store.load_project().unwrap().next_task_id, 4);
+        fs::remove_dir_all(store.dir()).ok();
+    }
+
+    #[test]
+    fn repair_refuses_links_to_duplicated_ids() {
+        for links in [r#","parent":2"#, r#","depends_on":[2]"#] {
+            let tasks = format!("[{},{},{}]", task(1, links), task(2, ""), task(2, ""));
+            let store = temp_store("repair-linked", &tasks);
+
+            let err = store.repair_task_ids().unwrap_err();
+
+            assert!(
+                err.to_string().contains("task 1 links to task 2"),
+                "{}",
+                err
+            );
+            assert_eq!(store.load_tasks().unwrap().len(), 3);
+            assert_eq!(
+                fs::read_to_string(store.dir().join(TASKS_FILE)).unwrap(),
+                tasks
+            );
+            fs::remove_dir_all(store.dir()).ok();
+        }
+    }
+
+    #[test]
+    fn updates_refuse_duplicate_ids() {
+        let tasks = format!("[{},{},{}]", task(1, ""), task(2, ""), task(2, ""));
+        let store = temp_store("update-duplicates", &tasks);
+
+        let err = store
+            .update_tasks(|tasks| {
+                tasks[2].description = "edited".into();
+                Ok(())
+            })
+            .unwrap_err();
+
+        assert!(err.to_string().contains("pmcli repair test"), "{}", err);
+        assert_eq!(
+            fs::read_to_string(store.dir().join(TASKS_FILE)).unwrap(),
+            tasks
+        );
+        assert!(store.load_history().unwrap().is_empty());
+        assert!(store.undo().unwrap().is_none());
+
+        store.repair_task_ids().unwrap();
+        store.update_tasks(|_| Ok(())).unwrap();
+        fs::remove_dir_all(store.dir()).ok();
+    }
+
+    #[test]
+    fn imports_keep_their_timestamps_and_can_be_undone() {
+        let store = temp_store("import", &format!("[{}]", task(1, "")));
+        let at = |s: &str| {
+            Some(
+                DateTime::parse_from_rfc3339(s)
+                    .unwrap()
+                    .with_timezone(&Local),
+            )
+        };
+        let old = at("2020-05-01T10:00:00Z");
+
+        let ids = store
+            .import_with_archive(|tasks, _| {
+                let ids = store.allocate_task_ids(tasks, 2)?;
+                for id in ids.clone() {
+                    let mut task = tasks[0].clone();
+                    task.id = id;
+                    task.created_at = old;
+                    task.updated_at = (id == 3).then_some(old).flatten();
+                    tasks.push(task);
+                }
+                Ok(ids)
+            })
+            .unwrap();
+
+        assert_eq!(ids, 3..5);
+        assert_eq!(store.load_project().unwrap().next_task_id, 5);
+        let tasks = store.load_tasks().unwrap();
+        assert_eq!(tasks[1].created_at, old);
+        assert_eq!(tasks[1].updated_at, old);
+        assert!(tasks[2].updated_at > old);
+        assert_eq!(tasks[0].updated_at, None);
+
+        store
+            .update_tasks(|tasks| {
+                tasks[1].description = "edited".into();
+                Ok(())
+            })
+            .unwrap();
+        assert!(store.load_tasks().unwrap()[1].updated_at > old);
+
+        store.undo().unwrap().unwrap();
+        assert_eq!(store.load_tasks().unwrap(), tasks);
+        store.undo().unwrap().unwrap();
+        assert_eq!(store.load_tasks().unwrap().len(), 1);
+        assert!(store.undo().unwrap().is_none());
+        store.redo().unwrap().unwrap();
+        assert_eq!(store.load_tasks().unwrap(), tasks);
+        fs::remove_dir_all(store.dir()).ok();
+    }
+
+    #[test]
+    fn stale_lock_is_removed() {
+        let dir = temp_dir("stale-lock");
+        let path = dir.join(LOCK_FILE);
+        let file = File::create(&path).unwrap();
+        file.set_modified(SystemTime::now() - LOCK_STALE_AFTER * 2)
+            .unwrap();
+
+        remove_stale_lock(&path);
+
+        assert!(!path.exists());
+        fs::remove_dir_all(dir).ok();
+    }
+
+    #[test]
+    fn fresh_lock_taken_by_another_waiter_is_put_back() {
+        let dir = temp_dir("fresh-lock");
+        let path = dir.join(LOCK_FILE);
+        fs::write(&path, "1234\n").unwrap();
+
+        remove_stale_lock(&path);
+
+        assert_eq!(fs::read_to_string(&path).unwrap(), "1234\n");
+        assert_eq!(fs::read_dir(&dir).unwrap().count(), 1);
+        fs::remove_dir_all(dir).ok();
+    }
+}
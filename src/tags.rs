@@ -0,0 +1,48 @@
+use crate::error::{PmError, Result};
+use crate::models::Task;
+
+/// Check a user-supplied tag and return it in stored form (lower case).
+///
+/// Tags are single words; a leading `!` is reserved for excluding a tag
+/// when filtering.
+pub fn normalize(tag: &str) -> Result<String> {
+    let tag = tag.trim();
+
+    if tag.is_empty() || tag.starts_with('!') || tag.chars().any(|c| c.is_whitespace() || c == ',')
+    {
+        return Err(PmError::InvalidInput(format!(
+            "invalid tag '{}' (use a single word without spaces, commas or a leading '!')",
+            tag
+        )));
+    }
+
+    Ok(tag.to_lowercase())
+}
+
+/// Tag filter from `--tag` arguments: `backend` requires a tag, `!frontend`
+/// excludes it.
+#[derive(Debug, Clone, Default)]
+pub struct TagFilter {
+    include: Vec<String>,
+    exclude: Vec<String>,
+}
+
+impl TagFilter {
+    pub fn parse(args: &[String]) -> Result<Self> {
+        let mut filter = TagFilter::default();
+
+        for arg in args {
+            match arg.trim().strip_prefix('!') {
+                Some(tag) => filter.exclude.push(normalize(tag)?),
+                None => filter.include.push(normalize(arg)?),
+            }
+        }
+
+        Ok(filter)
+    }
+
+    pub fn matches(&self, task: &Task) -> bool {
+        self.include.iter().all(|tag| task.tags.contains(tag))
+            && !self.exclude.iter().any(|tag| task.tags.contains(tag))
+    }
+}
@@ -0,0 +1,418 @@
+//! Taskwarrior's JSON format, as written by `task export` and read by
+//! `task import`.
+
+use std::collections::{HashMap, HashSet};
+
+use chrono::{DateTime, Local, NaiveDate, NaiveDateTime, TimeZone, Utc};
+use serde::{Deserialize, Deserializer, Serialize};
+use sha2::{Digest, Sha256};
+
+use crate::error::{PmError, Result};
+use crate::models::{Priority, StatusKind, Task};
+use crate::tags;
+use crate::workflow::Workflow;
+
+/// Timestamp format of Taskwarrior dates, always in UTC.
+const DATE_FORMAT: &str = "%Y%m%dT%H%M%SZ";
+
+/// A Taskwarrior task. Attributes pmcli has no field for, such as
+/// annotations or urgency, are left out.
+#[derive(Serialize, Deserialize, Debug, Clone, Default)]
+pub struct TwTask {
+    pub uuid: String,
+    pub description: String,
+    /// `pending`, `completed`, `deleted`, `waiting` or `recurring`.
+    pub status: String,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub entry: Option<String>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub modified: Option<String>,
+    /// Set while the task is being worked on.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub start: Option<String>,
+    /// When the task was completed or deleted.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub end: Option<String>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub due: Option<String>,
+    /// `H`, `M` or `L`.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub priority: Option<String>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub project: Option<String>,
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub tags: Vec<String>,
+    /// UUIDs of the tasks this one depends on.
+    #[serde(
+        default,
+        deserialize_with = "depends",
+        skip_serializing_if = "Vec::is_empty"
+    )]
+    pub depends: Vec<String>,
+}
+
+/// Read the output of `task export`: a JSON array, or one task per line as
+/// older versions write it.
+pub fn parse(data: &str) -> Result<Vec<TwTask>> {
+    let invalid =
+        |e: serde_json::Error| PmError::InvalidInput(format!("invalid Taskwarrior export: {}", e));
+
+    let tasks: Vec<TwTask> = if data.trim_start().starts_with('[') {
+        serde_json::from_str(data).map_err(invalid)?
+    } else {
+        data.lines()
+            .map(|line| line.trim().trim_end_matches(','))
+            .filter(|line| !line.is_empty())
+            .map(|line| serde_json::from_str(line).map_err(invalid))
+            .collect::<Result<_>>()?
+    };
+
+    let mut uuids = HashSet::new();
+    if let Some(task) = tasks.iter().find(|t| !uuids.insert(t.uuid.as_str())) {
+        return Err(PmError::InvalidInput(format!(
+            "invalid Taskwarrior export: UUID {} appears twice",
+            task.uuid
+        )));
+    }
+
+    Ok(tasks)
+}
+
+/// Convert Taskwarrior tasks to pmcli tasks numbered from 1 in file order.
+///
+/// Statuses map to the first status of the matching kind in `workflow`:
+/// started pending tasks are active, waiting ones blocked, deleted ones
+/// cancelled. Dependencies on tasks outside `tasks` are dropped.
+pub fn to_tasks(
+    tasks: &[TwTask],
+    workflow: &Workflow,
+    default_priority: Priority,
+    owner: &str,
+) -> Result<Vec<Task>> {
+    let ids: HashMap<&str, u32> = tasks
+        .iter()
+        .zip(1..)
+        .map(|(t, id)| (t.uuid.as_str(), id))
+        .collect();
+
+    tasks
+        .iter()
+        .zip(1..)
+        .map(|(tw, id)| {
+            let kind = match tw.status.as_str() {
+                "completed" => StatusKind::Done,
+                "deleted" => StatusKind::Cancelled,
+                "waiting" => StatusKind::Blocked,
+                "pending" | "recurring" if tw.start.is_some() => StatusKind::Active,
+                "pending" | "recurring" => StatusKind::Todo,
+                other => {
+                    return Err(PmError::InvalidInput(format!(
+                        "task {}: unknown Taskwarrior status '{}'",
+                        tw.uuid, other
+                    )))
+                }
+            };
+            let status = workflow
+                .first_of_kind(kind)
+                .unwrap_or(workflow.initial())
+                .clone();
+
+            let priority = match tw.priority.as_deref() {
+                Some("H") => Priority::High,
+                Some("M") => Priority::Medium,
+                Some("L") => Priority::Low,
+                _ => default_priority,
+            };
+
+            let mut task_tags = Vec::new();
+            for tag in &tw.tags {
+                let tag = tags::normalize(tag)?;
+                if !task_tags.contains(&tag) {
+                    task_tags.push(tag);
+                }
+            }
+
+            Ok(Task {
+                id,
+                description: tw.description.clone(),
+                status,
+                priority,
+                deadline: date(&tw.due)?.map(|d| d.date_naive()),
+                owner: owner.to_string(),
+                parent: None,
+                depends_on: tw
+                    .depends
+                    .iter()
+                    .filter_map(|uuid| ids.get(uuid.as_str()).copied())
+                    .collect(),
+                tags: task_tags,
+                created_at: date(&tw.entry)?,
+                updated_at: date(&tw.modified)?,
+                completed_at: match kind {
+                    StatusKind::Done => date(&tw.end)?,
+                    _ => None,
+                },
+                archived_at: None,
+                uuid: Some(tw.uuid.clone()),
+            })
+        })
+        .collect()
+}
+
+/// `tasks` of `project` as a Taskwarrior export. `all` holds every task of
+/// the project, to look up the UUIDs of dependencies.
+///
+/// Blocked tasks are written as pending, as Taskwarrior only knows waiting
+/// until a date.
+pub fn export(project: &str, tasks: &[Task], all: &[Task], workflow: &Workflow) -> Result<String> {
+    let now = Local::now();
+
+    let tw: Vec<TwTask> = tasks
+        .iter()
+        .map(|task| {
+            let kind = workflow.kind(&task.status);
+            let status = match kind {
+                StatusKind::Done => "completed",
+                StatusKind::Cancelled => "deleted",
+                _ => "pending",
+            };
+
+            TwTask {
+                uuid: uuid(project, task),
+                description: task.description.clone(),
+                status: status.to_string(),
+                entry: Some(timestamp(task.created_at.unwrap_or(now))),
+                modified: task.updated_at.map(timestamp),
+                start: (kind == StatusKind::Active)
+                    .then(|| timestamp(task.updated_at.unwrap_or(now))),
+                end: kind
+                    .is_closed()
+                    .then(|| timestamp(task.completed_at.or(task.updated_at).unwrap_or(now))),
+                due: task.deadline.and_then(midnight).map(timestamp),
+                priority: Some(priority(task.priority).to_string()),
+                project: Some(project.to_string()),
+                tags: task.tags.clone(),
+                depends: task
+                    .depends_on
+                    .iter()
+                    .map(|id| match all.iter().find(|t| t.id == *id) {
+                        Some(dependency) => uuid(project, dependency),
+                        None => derived_uuid(project, *id),
+                    })
+                    .collect(),
+            }
+        })
+        .collect();
+
+    let mut json = serde_json::to_string_pretty(&tw).map_err(std::io::Error::from)?;
+    json.push('\n');
+    Ok(json)
+}
+
+/// The UUID a task was imported with, or one derived from the project and
+/// ID so it stays the same between exports.
+pub fn uuid(project: &str, task: &Task) -> String {
+    task.uuid
+        .clone()
+        .unwrap_or_else(|| derived_uuid(project, task.id))
+}
+
+/// Name-based UUID (version 8, SHA-256) of a pmcli task.
+fn derived_uuid(project: &str, id: u32) -> String {
+    let hash = Sha256::digest(format!("pmcli:{}:{}", project, id));
+    let mut bytes = [0u8; 16];
+    bytes.copy_from_slice(&hash[..16]);
+    bytes[6] = (bytes[6] & 0x0f) | 0x80;
+    bytes[8] = (bytes[8] & 0x3f) | 0x80;
+
+    let hex: String = bytes.iter().map(|b| format!("{:02x}", b)).collect();
+    format!(
+        "{}-{}-{}-{}-{}",
+        &hex[..8],
+        &hex[8..12],
+        &hex[12..16],
+        &hex[16..20],
+        &hex[20..]
+    )
+}
+
+/// Taskwarrior only has three priorities: levels 7 and up are high, 5 and
+/// 6 medium, the rest low.
+fn priority(priority: Priority) -> &'static str {
+    match priority.level() {
+        7.. => "H",
+        5..=6 => "M",
+        _ => "L",
+    }
+}
+
+fn midnight(date: NaiveDate) -> Option<DateTime<Local>> {
+    Local
+        .from_local_datetime(&date.and_hms_opt(0, 0, 0)?)
+        .earliest()
+}
+
+fn timestamp(at: DateTime<Local>) -> String {
+    at.with_timezone(&Utc).format(DATE_FORMAT).to_string()
+}
+
+fn date(value: &Option<String>) -> Result<Option<DateTime<Local>>> {
+    value
+        .as_deref()
+        .map(|v| {
+            NaiveDateTime::parse_from_str(v, DATE_FORMAT)
+                .map(|t| Utc.from_utc_datetime(&t).with_timezone(&Local))
+                .map_err(|_| {
+                    PmError::InvalidInput(format!(
+                        "invalid Taskwarrior date '{}' (expected YYYYMMDDTHHMMSSZ)",
+                        v
+                    ))
+                })
+        })
+        .transpose()
+}
+
+/// Taskwarrior 2.6 writes `depends` as an array, older versions as a
+/// comma-separated string.
+fn depends<'de, D: Deserializer<'de>>(
+    deserializer: D,
+) -> std::result::Result<Vec<String>, D::Error> {
+    #[derive(Deserialize)]
+    #[serde(untagged)]
+    enum Raw {
+        List(Vec<String>),
+        Joined(String),
+    }
+
+    Ok(match Raw::deserialize(deserializer)? {
+        Raw::List(uuids) => uuids,
+        Raw::Joined(uuids) => uuids
+            .split(',')
+            .map(|u| u.trim().to_string())
+            .filter(|u| !u.is_empty())
+            .collect(),
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const EXPORT: &str = r#"[
+{"uuid":"a1","description":"Write docs","status":"pending","entry":"20240301T093015Z","modified":"20240302T100000Z","priority":"H","tags":["Docs","docs"],"depends":"b2,zz"},
+{"uuid":"b2","description":"Review","status":"completed","entry":"20240301T093015Z","end":"20240303T120000Z","due":"20240310T000000Z"},
+{"uuid":"c3","description":"Spike","status":"pending","start":"20240301T100000Z","depends":["a1"]},
+{"uuid":"d4","description":"Old idea","status":"deleted","end":"20240304T000000Z"},
+{"uuid":"e5","description":"Later","status":"waiting"}
+]"#;
+
+    fn tasks() -> Vec<Task> {
+        let tw = parse(EXPORT).unwrap();
+        to_tasks(&tw, &Workflow::default(), Priority::Low, "me").unwrap()
+    }
+
+    fn at(s: &str) -> Option<DateTime<Local>> {
+        date(&Some(s.to_string())).unwrap()
+    }
+
+    #[test]
+    fn maps_taskwarrior_fields() {
+        let tasks = tasks();
+
+        let statuses: Vec<String> = tasks.iter().map(|t| t.status.to_string()).collect();
+        assert_eq!(
+            statuses,
+            ["todo", "done", "in-progress", "cancelled", "blocked"]
+        );
+        assert_eq!(tasks[0].priority, Priority::High);
+        assert_eq!(tasks[1].priority, Priority::Low);
+        assert_eq!(tasks[0].tags, ["docs"]);
+        assert_eq!(tasks[0].depends_on, [2]);
+        assert_eq!(tasks[2].depends_on, [1]);
+        assert_eq!(tasks[0].created_at, at("20240301T093015Z"));
+        assert_eq!(tasks[0].updated_at, at("20240302T100000Z"));
+        assert_eq!(tasks[1].completed_at, at("20240303T120000Z"));
+        assert_eq!(tasks[3].completed_at, None);
+        assert_eq!(
+            tasks[1].deadline,
+            at("20240310T000000Z").map(|d| d.date_naive())
+        );
+        assert_eq!(tasks[4].uuid.as_deref(), Some("e5"));
+    }
+
+    #[test]
+    fn reads_one_task_per_line() {
+        let lines = "{\"uuid\":\"a\",\"description\":\"x\",\"status\":\"pending\"},\n\n\
+                     {\"uuid\":\"b\",\"description\":\"y\",\"status\":\"pending\"}\n";
+
+        let tw = parse(lines).unwrap();
+
+        assert_eq!(tw.len(), 2);
+        assert_eq!(tw[1].uuid, "b");
+    }
+
+    #[test]
+    fn rejects_bad_input() {
+        let convert = |json: &str| {
+            to_tasks(&parse(json)?, &Workflow::default(), Priority::Low, "me").map(|_| ())
+        };
+
+        assert!(parse("[{\"uuid\":\"a\"}]").is_err());
+        assert_eq!(
+            parse(
+                r#"[{"uuid":"a","description":"x","status":"pending"},
+                    {"uuid":"a","description":"y","status":"pending"}]"#
+            )
+            .unwrap_err()
+            .to_string(),
+            "invalid Taskwarrior export: UUID a appears twice"
+        );
+        assert!(convert(r#"[{"uuid":"a","description":"x","status":"lost"}]"#).is_err());
+        assert!(convert(
+            r#"[{"uuid":"a","description":"x","status":"pending","entry":"2024-03-01"}]"#
+        )
+        .is_err());
+    }
+
+    #[test]
+    fn export_reads_back_with_the_same_uuids() {
+        let mut tasks = tasks();
+        tasks[4].uuid = None;
+        let workflow = Workflow::default();
+
+        let json = export("web", &tasks, &tasks, &workflow).unwrap();
+        let tw = parse(&json).unwrap();
+        let again = to_tasks(&tw, &workflow, Priority::Medium, "me").unwrap();
+
+        assert_eq!(tw[0].depends, ["b2"]);
+        assert_eq!(tw[4].uuid, derived_uuid("web", 5));
+        assert_eq!(tw[4].uuid, uuid("web", &tasks[4]));
+        assert_eq!(tw[1].priority.as_deref(), Some("L"));
+        // Blocked has no Taskwarrior counterpart
+        assert_eq!(tw[4].status, "pending");
+
+        for (before, after) in tasks.iter().zip(&again).take(4) {
+            assert_eq!(after.uuid, before.uuid);
+            assert_eq!(after.status, before.status);
+            assert_eq!(after.depends_on, before.depends_on);
+            assert_eq!(after.deadline, before.deadline);
+            assert_eq!(after.completed_at, before.completed_at);
+            // Taskwarrior needs an entry date, so unknown ones become now
+            if before.created_at.is_some() {
+                assert_eq!(after.created_at, before.created_at);
+            }
+        }
+    }
+
+    #[test]
+    fn derived_uuids_are_stable_version_8() {
+        let uuid = derived_uuid("web", 3);
+
+        assert_eq!(uuid, derived_uuid("web", 3));
+        assert_ne!(uuid, derived_uuid("web", 4));
+        assert_ne!(uuid, derived_uuid("api", 3));
+        assert_eq!(uuid.len(), 36);
+        assert_eq!(&uuid[14..15], "8");
+        assert!(matches!(&uuid[19..20], "8" | "9" | "a" | "b"));
+    }
+}
@@ -0,0 +1,61 @@
+//! Fixtures shared by the unit tests.
+
+use std::fs;
+use std::path::PathBuf;
+
+use chrono::Local;
+
+use crate::models::{Config, Priority, Project, Status, Task};
+use crate::storage::ProjectStore;
+
+/// A medium-priority task owned by `me`, with every optional field empty.
+pub(crate) fn task(id: u32, status: Status) -> Task {
+    Task {
+        id,
+        description: "t".into(),
+        status,
+        priority: Priority::Medium,
+        deadline: None,
+        owner: "me".into(),
+        parent: None,
+        depends_on: Vec::new(),
+        tags: Vec::new(),
+        created_at: None,
+        updated_at: None,
+        completed_at: None,
+        archived_at: None,
+        uuid: None,
+    }
+}
+
+/// Settings as if there were no `config.toml`.
+pub(crate) fn config() -> Config {
+    Config {
+        default_priority: None,
+        date_format: None,
+        tag_colors: Default::default(),
+        queries: Default::default(),
+    }
+}
+
+/// An empty directory of its own for the test `name`.
+pub(crate) fn temp_dir(name: &str) -> PathBuf {
+    let dir = std::env::temp_dir().join(format!("pmcli-test-{}-{}", name, std::process::id()));
+    fs::remove_dir_all(&dir).ok();
+    fs::create_dir_all(&dir).unwrap();
+    dir
+}
+
+/// A project without tasks in a temporary directory.
+pub(crate) fn temp_store(name: &str) -> ProjectStore {
+    let store = ProjectStore::at_dir("test", temp_dir(name));
+    store
+        .save_project(&Project {
+            name: "test".into(),
+            created_at: Local::now(),
+            next_task_id: 1,
+        })
+        .unwrap();
+    store.save_tasks(&[]).unwrap();
+    store
+}
@@ -0,0 +1,131 @@
+use std::collections::HashSet;
+
+use crate::error::{PmError, Result};
+use crate::models::{Status, StatusKind, Task};
+use crate::workflow::Workflow;
+
+/// Completion of a task's subtasks.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Progress {
+    pub done: usize,
+    /// Subtasks counted, cancelled ones excluded.
+    pub total: usize,
+}
+
+impl Progress {
+    pub fn percent(&self) -> u32 {
+        if self.total == 0 {
+            return 100;
+        }
+        (self.done * 100 / self.total) as u32
+    }
+}
+
+/// All subtasks of `id`, children first, then their children.
+pub fn descendants(tasks: &[Task], id: u32) -> Vec<&Task> {
+    let mut found = Vec::new();
+    let mut seen = HashSet::from([id]);
+    let mut parents = vec![id];
+
+    // `seen` guards against parent loops in hand-edited files
+    while let Some(parent) = parents.pop() {
+        for task in tasks.iter().filter(|t| t.parent == Some(parent)) {
+            if seen.insert(task.id) {
+                found.push(task);
+                parents.push(task.id);
+            }
+        }
+    }
+
+    found
+}
+
+/// Share of done subtasks of `id`, or `None` when it has none.
+pub fn progress(tasks: &[Task], workflow: &Workflow, id: u32) -> Option<Progress> {
+    let subtasks = descendants(tasks, id);
+    if subtasks.is_empty() {
+        return None;
+    }
+
+    let counted: Vec<StatusKind> = subtasks
+        .iter()
+        .map(|t| workflow.kind(&t.status))
+        .filter(|kind| *kind != StatusKind::Cancelled)
+        .collect();
+
+    Some(Progress {
+        done: counted.iter().filter(|k| **k == StatusKind::Done).count(),
+        total: counted.len(),
+    })
+}
+
+/// Refuse moving task `id` to a done status while it has open subtasks.
+pub fn check_subtasks_closed(
+    tasks: &[Task],
+    workflow: &Workflow,
+    id: u32,
+    to: &Status,
+) -> Result<()> {
+    if workflow.kind(to) != StatusKind::Done {
+        return Ok(());
+    }
+
+    let open: Vec<String> = descendants(tasks, id)
+        .iter()
+        .filter(|t| !workflow.is_closed(&t.status))
+        .map(|t| t.id.to_string())
+        .collect();
+
+    if open.is_empty() {
+        return Ok(());
+    }
+
+    Err(PmError::InvalidInput(format!(
+        "task {} has open subtasks ({}), complete them first or force it",
+        id,
+        open.join(", ")
+    )))
+}
+
+/// Tasks in tree order with their depth: every task is followed by its
+/// subtasks, siblings keep their order in `tasks`. Tasks whose parent is
+/// not in `tasks` (filtered out, archived or deleted) are shown as roots.
+pub fn flatten<'a>(tasks: impl IntoIterator<Item = &'a Task>) -> Vec<(usize, &'a Task)> {
+    let tasks: Vec<&Task> = tasks.into_iter().collect();
+    let ids: HashSet<u32> = tasks.iter().map(|t| t.id).collect();
+    let mut rows = Vec::with_capacity(tasks.len());
+    let mut seen = HashSet::new();
+
+    for root in tasks
+        .iter()
+        .filter(|t| !t.parent.is_some_and(|p| ids.contains(&p)))
+    {
+        push_subtree(&tasks, root, 0, &mut seen, &mut rows);
+    }
+
+    // Tasks caught in a parent loop have no root; list them at the end
+    for task in &tasks {
+        if !seen.contains(&task.id) {
+            push_subtree(&tasks, task, 0, &mut seen, &mut rows);
+        }
+    }
+
+    rows
+}
+
+fn push_subtree<'a>(
+    tasks: &[&'a Task],
+    task: &'a Task,
+    depth: usize,
+    seen: &mut HashSet<u32>,
+    rows: &mut Vec<(usize, &'a Task)>,
+) {
+    if !seen.insert(task.id) {
+        return;
+    }
+    rows.push((depth, task));
+
+    for child in tasks.iter().filter(|t| t.parent == Some(task.id)) {
+        push_subtree(tasks, child, depth + 1, seen, rows);
+    }
+}
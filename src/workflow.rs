@@ -0,0 +1,278 @@
+use std::collections::{BTreeMap, HashSet};
+
+use serde::{Deserialize, Serialize};
+
+use crate::error::{PmError, Result};
+use crate::models::{status_key, Status, StatusKind};
+
+/// Per-project board definition, stored as `workflow.toml`.
+///
+/// ```toml
+/// [[status]]
+/// name = "Todo"
+///
+/// [[status]]
+/// name = "Review"
+/// kind = "active"
+/// color = "cyan"
+///
+/// [[status]]
+/// name = "Done"
+///
+/// [transitions]
+/// Todo = ["Review"]
+/// Review = ["Todo", "Done"]
+/// ```
+///
+/// Statuses are listed in board order and the first one is where new and
+/// reopened tasks start. A status without a `transitions` entry may move to
+/// any other status.
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
+pub struct Workflow {
+    #[serde(rename = "status")]
+    pub statuses: Vec<WorkflowStatus>,
+    #[serde(default, skip_serializing_if = "BTreeMap::is_empty")]
+    pub transitions: BTreeMap<String, Vec<String>>,
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
+pub struct WorkflowStatus {
+    pub name: Status,
+    /// Defaults to the built-in kind of `name`, or `todo` for custom names.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub kind: Option<StatusKind>,
+    /// Colour name for the TUI (`cyan`, `lightblue`, `#ff8800`, ...).
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub color: Option<String>,
+}
+
+impl Default for Workflow {
+    /// The built-in statuses with every transition allowed.
+    fn default() -> Self {
+        Workflow {
+            statuses: Status::BUILT_IN
+                .iter()
+                .map(|status| WorkflowStatus {
+                    name: status.clone(),
+                    kind: None,
+                    color: None,
+                })
+                .collect(),
+            transitions: BTreeMap::new(),
+        }
+    }
+}
+
+impl Workflow {
+    /// Check that the definition is usable; returns a description of the
+    /// first problem found.
+    pub fn validate(&self) -> std::result::Result<(), String> {
+        if self.statuses.is_empty() {
+            return Err("workflow defines no statuses".into());
+        }
+
+        let mut seen = HashSet::new();
+        for s in &self.statuses {
+            if s.name.name().trim().is_empty() {
+                return Err("status names cannot be empty".into());
+            }
+            if !seen.insert(status_key(s.name.name())) {
+                return Err(format!("status '{}' is defined twice", s.name.name()));
+            }
+        }
+
+        for (from, targets) in &self.transitions {
+            for name in std::iter::once(from).chain(targets) {
+                if !self.contains(&Status::from_name(name)) {
+                    return Err(format!("transition uses undefined status '{}'", name));
+                }
+            }
+        }
+
+        Ok(())
+    }
+
+    pub fn contains(&self, status: &Status) -> bool {
+        self.statuses.iter().any(|s| s.name == *status)
+    }
+
+    /// Where new and reopened tasks start.
+    pub fn initial(&self) -> &Status {
+        &self.statuses[0].name
+    }
+
+    pub fn kind(&self, status: &Status) -> StatusKind {
+        self.statuses
+            .iter()
+            .find(|s| s.name == *status)
+            .and_then(|s| s.kind)
+            .or_else(|| status.builtin_kind())
+            .unwrap_or_default()
+    }
+
+    /// Index of `status` in board order; unknown statuses sort last.
+    pub fn position(&self, status: &Status) -> usize {
+        self.statuses
+            .iter()
+            .position(|s| s.name == *status)
+            .unwrap_or(self.statuses.len())
+    }
+
+    pub fn is_closed(&self, status: &Status) -> bool {
+        self.kind(status).is_closed()
+    }
+
+    /// First status of the given kind, in board order.
+    pub fn first_of_kind(&self, kind: StatusKind) -> Option<&Status> {
+        self.statuses
+            .iter()
+            .map(|s| &s.name)
+            .find(|s| self.kind(s) == kind)
+    }
+
+    pub fn color(&self, status: &Status) -> Option<&str> {
+        self.statuses
+            .iter()
+            .find(|s| s.name == *status)
+            .and_then(|s| s.color.as_deref())
+    }
+
+    /// Look up a user-supplied status name, ignoring case and `-`/`_`/spaces.
+    pub fn resolve(&self, input: &str) -> Result<Status> {
+        let key = status_key(input);
+
+        if let Some(s) = self
+            .statuses
+            .iter()
+            .find(|s| status_key(s.name.name()) == key)
+        {
+            return Ok(s.name.clone());
+        }
+
+        // Built-in aliases such as `doing` or `canceled`
+        match input.parse::<Status>() {
+            Ok(status) if self.contains(&status) => Ok(status),
+            _ => Err(PmError::InvalidInput(format!(
+                "unknown status '{}' (expected {})",
+                input,
+                self.names().join(", ")
+            ))),
+        }
+    }
+
+    /// The `transitions` entry for `from`, however its name is written.
+    pub fn transitions_from(&self, from: &Status) -> Option<&Vec<String>> {
+        self.transitions
+            .iter()
+            .find(|(name, _)| Status::from_name(name) == *from)
+            .map(|(_, allowed)| allowed)
+    }
+
+    pub fn can_move(&self, from: &Status, to: &Status) -> bool {
+        if from == to {
+            return true;
+        }
+        if !self.contains(to) {
+            return false;
+        }
+
+        match self.transitions_from(from) {
+            Some(allowed) => allowed.iter().any(|name| Status::from_name(name) == *to),
+            None => true,
+        }
+    }
+
+    pub fn check_transition(&self, from: &Status, to: &Status) -> Result<()> {
+        if !self.contains(to) {
+            return Err(PmError::InvalidInput(format!(
+                "status '{}' is not part of this project's workflow (expected {})",
+                to,
+                self.names().join(", ")
+            )));
+        }
+
+        if !self.can_move(from, to) {
+            return Err(PmError::InvalidInput(format!(
+                "workflow does not allow moving from '{}' to '{}'",
+                from, to
+            )));
+        }
+
+        Ok(())
+    }
+
+    /// Next status when stepping a task forward (TUI Enter).
+    ///
+    /// Walks the board order from the current status, wrapping around, and
+    /// picks the first allowed status that is neither blocked nor
+    /// cancelled.
+    pub fn next_in_cycle(&self, from: &Status) -> Option<&Status> {
+        let start = self
+            .statuses
+            .iter()
+            .position(|s| s.name == *from)
+            .map(|i| i + 1)
+            .unwrap_or(0);
+
+        (0..self.statuses.len())
+            .map(|offset| &self.statuses[(start + offset) % self.statuses.len()].name)
+            .filter(|s| *s != from)
+            .filter(|s| !matches!(self.kind(s), StatusKind::Blocked | StatusKind::Cancelled))
+            .find(|s| self.can_move(from, s))
+    }
+
+    fn names(&self) -> Vec<String> {
+        self.statuses.iter().map(|s| s.name.to_string()).collect()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn built_in_names_match_in_any_case() {
+        let workflow: Workflow = toml::from_str(
+            r#"
+            [[status]]
+            name = "todo"
+
+            [[status]]
+            name = "in-progress"
+
+            [[status]]
+            name = "DONE"
+
+            [transitions]
+            todo = ["In Progress"]
+            in_progress = ["done"]
+            "#,
+        )
+        .unwrap();
+        workflow.validate().unwrap();
+
+        assert_eq!(workflow.initial(), &Status::Todo);
+        assert!(workflow.contains(&Status::InProgress));
+        assert_eq!(workflow.kind(&Status::Done), StatusKind::Done);
+        assert!(workflow.is_closed(&Status::Done));
+        assert!(workflow.can_move(&Status::Todo, &Status::InProgress));
+        assert!(!workflow.can_move(&Status::Todo, &Status::Done));
+        assert!(workflow.can_move(&Status::InProgress, &Status::Done));
+        assert_eq!(
+            workflow.transitions_from(&Status::Todo),
+            Some(&vec!["In Progress".to_string()])
+        );
+
+        let shadowing: Workflow = toml::from_str(
+            r#"
+            [[status]]
+            name = "Todo"
+
+            [[status]]
+            name = "todo"
+            "#,
+        )
+        .unwrap();
+        assert!(shadowing.validate().is_err());
+    }
+}